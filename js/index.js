@@ -1 +1,6 @@
-import("../pkg/index.js").catch(console.error);
+import("../pkg/index.js")
+  .then((wasm) => {
+    // So the profiler can be read from the console as walkTheDog.profile()
+    window.walkTheDog = wasm;
+  })
+  .catch(console.error);
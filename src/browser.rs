@@ -8,8 +8,8 @@ use wasm_bindgen::{
 };
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, HtmlElement, HtmlImageElement,
-    Response, Window,
+    CanvasRenderingContext2d, Document, Gamepad, GamepadButton, HtmlCanvasElement,
+    HtmlImageElement, MouseEvent, Response, Storage, Window,
 };
 
 #[allow(unused_macros)]
@@ -106,6 +106,46 @@ pub fn new_image() -> Result<HtmlImageElement> {
     HtmlImageElement::new().map_err(|err| anyhow!("Could not create HtmlImageElement: {:#?}", err))
 }
 
+// The RGBA pixels of part of an image, read back from a canvas of its own
+pub fn image_pixels(
+    image: &HtmlImageElement,
+    x: f64,
+    y: f64,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>> {
+    let canvas = document()?
+        .create_element("canvas")
+        .map_err(|err| anyhow!("Could not create canvas {:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+
+    let context = canvas
+        .get_context("2d")
+        .map_err(|js_value| anyhow!("Error getting 2d context {:#?}", js_value))?
+        .ok_or_else(|| anyhow!("No 2d context found"))?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(|js_value| {
+            anyhow!(
+                "Error converting {:#?} to CanvasRenderingContext2d",
+                js_value
+            )
+        })?;
+    let (width, height) = (width.into(), height.into());
+    context
+        .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+            image, x, y, width, height, 0.0, 0.0, width, height,
+        )
+        .map_err(|err| anyhow!("Could not draw image {:#?}", err))?;
+
+    let image_data = context
+        .get_image_data(0.0, 0.0, width, height)
+        .map_err(|err| anyhow!("Could not read image data {:#?}", err))?;
+    Ok(image_data.data().0)
+}
+
 pub fn closure_once<F, A, R>(fn_once: F) -> Closure<F::FnMut>
 where
     F: 'static + WasmClosureFnOnce<A, R>,
@@ -136,47 +176,52 @@ pub fn now() -> Result<f64> {
         .now())
 }
 
-pub fn draw_ui(html: &str) -> Result<()> {
-    find_ui()?
-        .insert_adjacent_html("afterbegin", html)
-        .map_err(|err| anyhow!("Could not insert html {:#?}", err))
-}
-
-pub fn hide_ui() -> Result<()> {
-    let ui = find_ui()?;
-
-    if let Some(child) = ui.first_child() {
-        ui.remove_child(&child)
-            .map(|_removed_child| ())
-            .map_err(|err| anyhow!("Failed to remove child {:#?}", err))
-            .and_then(|_unit| {
-                canvas()?
-                    .focus()
-                    .map_err(|err| anyhow!("Could not set focus to canvas! {:#?}", err))
-            })
-    } else {
-        Ok(())
-    }
+// Where on the canvas the pointer is, in canvas pixels however big it's shown
+pub fn canvas_position(canvas: &HtmlCanvasElement, event: &MouseEvent) -> (i32, i32) {
+    let scale = |size: u32, shown: i32| {
+        if shown > 0 {
+            f64::from(size) / f64::from(shown)
+        } else {
+            1.0
+        }
+    };
+    (
+        (f64::from(event.offset_x()) * scale(canvas.width(), canvas.client_width())) as i32,
+        (f64::from(event.offset_y()) * scale(canvas.height(), canvas.client_height())) as i32,
+    )
 }
 
-fn find_ui() -> Result<Element> {
-    document().and_then(|doc| {
-        doc.get_element_by_id("ui")
-            .ok_or_else(|| anyhow!("UI element not found"))
-    })
+// Which buttons of the first connected gamepad are down, none if there isn't one
+pub fn gamepad_buttons() -> Result<Vec<bool>> {
+    let gamepads = window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Could not get gamepads {:#?}", err))?;
+    let gamepad = match gamepads
+        .iter()
+        .find_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+    {
+        Some(gamepad) => gamepad,
+        None => return Ok(vec![]),
+    };
+
+    Ok(gamepad
+        .buttons()
+        .iter()
+        .map(|button| {
+            button
+                .dyn_into::<GamepadButton>()
+                .map(|button| button.pressed())
+                .unwrap_or(false)
+        })
+        .collect())
 }
 
-pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
-    document()
-        .and_then(|doc| {
-            doc.get_element_by_id(id)
-                .ok_or_else(|| anyhow!("Element with id {} not found", id))
-        })
-        .and_then(|element| {
-            element
-                .dyn_into::<HtmlElement>()
-                .map_err(|err| anyhow!("Could not cast into HtmlElement {:#?}", err))
-        })
+pub fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Could not get local storage {:#?}", err))?
+        .ok_or_else(|| anyhow!("No local storage"))
 }
 
 #[cfg(test)]
@@ -1,4 +1,10 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Mutex};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    rc::Rc,
+    sync::Mutex,
+};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -7,7 +13,7 @@ use futures::channel::{
     oneshot::channel,
 };
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
-use web_sys::{AudioBuffer, AudioContext, HtmlElement, HtmlImageElement};
+use web_sys::{AudioBuffer, AudioContext, GainNode, HtmlImageElement};
 
 use crate::{
     browser::{self, LoopClosure},
@@ -17,9 +23,20 @@ use crate::{
 
 use web_sys::CanvasRenderingContext2d;
 
+use self::mask::CollisionMask;
+
+pub mod broad_phase;
+pub mod mask;
+pub mod physics;
+pub mod profiler;
+pub mod scene;
+pub mod ui;
+
 pub enum KeyPress {
     KeyUp(web_sys::KeyboardEvent),
     KeyDown(web_sys::KeyboardEvent),
+    PointerMove(Point),
+    PointerDown(Point),
 }
 
 pub struct Renderer {
@@ -58,19 +75,6 @@ impl Renderer {
             .expect("Drawing is throwing exceptions! Unrecoverable error.");
     }
 
-    pub fn _draw_rect(&self, bounding_box: &Rect) {
-        self.context.set_stroke_style(&JsValue::from_str("#FF0000"));
-        self.context.begin_path();
-        self.context.rect(
-            bounding_box.x().into(),
-            bounding_box.y().into(),
-            bounding_box.width().into(),
-            bounding_box.height().into(),
-        );
-        self.context.stroke();
-    }
-
-    #[allow(dead_code)]
     pub fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
         self.context.set_font("16 pt serif");
         self.context
@@ -78,6 +82,99 @@ impl Renderer {
             .map_err(|err| anyhow!("Error filling text {:#?}", err))?;
         Ok(())
     }
+
+    // `font` is a CSS font, like "48px 'Ken Future'"
+    pub fn draw_styled_text(
+        &self,
+        text: &str,
+        location: &Point,
+        font: &str,
+        color: &str,
+    ) -> Result<()> {
+        self.context.save();
+        self.context.set_font(font);
+        self.context.set_fill_style(&JsValue::from_str(color));
+        let drawn = self
+            .context
+            .fill_text(text, location.x.into(), location.y.into())
+            .map_err(|err| anyhow!("Error filling text {:#?}", err));
+        self.context.restore();
+        drawn
+    }
+
+    pub fn fill_rect(&self, rect: &Rect, color: &str) {
+        self.context.save();
+        self.context.set_fill_style(&JsValue::from_str(color));
+        self.context.fill_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width().into(),
+            rect.height().into(),
+        );
+        self.context.restore();
+    }
+
+    pub fn stroke_rect(&self, rect: &Rect, color: &str) {
+        self.context.save();
+        self.context.set_stroke_style(&JsValue::from_str(color));
+        self.context.stroke_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width().into(),
+            rect.height().into(),
+        );
+        self.context.restore();
+    }
+
+    pub fn stroke_circle(&self, center: &Point, radius: i32, color: &str) {
+        self.context.save();
+        self.context.set_stroke_style(&JsValue::from_str(color));
+        self.context.begin_path();
+        self.context
+            .arc(
+                center.x.into(),
+                center.y.into(),
+                radius.into(),
+                0.0,
+                std::f64::consts::TAU,
+            )
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+        self.context.stroke();
+        self.context.restore();
+    }
+
+    pub fn stroke_path(&self, points: &[Point], color: &str) {
+        let (first, rest) = match points.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        self.context.save();
+        self.context.set_stroke_style(&JsValue::from_str(color));
+        self.context.begin_path();
+        self.context.move_to(first.x.into(), first.y.into());
+        rest.iter()
+            .for_each(|point| self.context.line_to(point.x.into(), point.y.into()));
+        self.context.stroke();
+        self.context.restore();
+    }
+
+    pub fn fill_circle(&self, center: &Point, radius: i32, color: &str) {
+        self.context.save();
+        self.context.set_fill_style(&JsValue::from_str(color));
+        self.context.begin_path();
+        self.context
+            .arc(
+                center.x.into(),
+                center.y.into(),
+                radius.into(),
+                0.0,
+                std::f64::consts::TAU,
+            )
+            .expect("Drawing is throwing exceptions! Unrecoverable error.");
+        self.context.fill();
+        self.context.restore();
+    }
 }
 
 #[async_trait(?Send)]
@@ -88,6 +185,11 @@ pub trait Game {
 }
 
 const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
+// After a long gap, like a hidden tab, the game picks up where it was rather
+// than running every update it missed in one burst
+const MOST_CATCH_UP: f32 = FRAME_SIZE * 5.0;
+// Shows or hides the frame time overlay, in any game
+const PROFILER_KEY: &str = "KeyF";
 
 pub struct GameLoop {
     last_frame: f64,
@@ -117,22 +219,41 @@ impl GameLoop {
         let mut keystate = KeyState::new();
         *g.borrow_mut() = Some(browser::create_raf_closure(move |perf| {
             process_input(&mut keystate, &mut keyevent_receiver);
+            // A browser without gamepads is the same as no gamepad plugged in
+            if let Ok(buttons) = browser::gamepad_buttons() {
+                keystate.set_gamepad(buttons);
+            }
 
             let frame_time = perf - game_loop.last_frame;
-            game_loop.accumulated_delta += frame_time as f32;
+            game_loop.accumulated_delta =
+                (game_loop.accumulated_delta + frame_time as f32).min(MOST_CATCH_UP);
+            // The frame's own timestamp is the best guess if the clock goes missing
+            let clock = || browser::now().unwrap_or(perf);
 
+            let update_start = clock();
+            let mut steps = 0;
             while game_loop.accumulated_delta > FRAME_SIZE {
+                if keystate.was_just_pressed(PROFILER_KEY) {
+                    profiler::toggle();
+                }
                 game.update(&keystate);
+                keystate.clear_just_pressed();
                 game_loop.accumulated_delta -= FRAME_SIZE;
+                steps += 1;
             }
             game_loop.last_frame = perf;
+
+            let draw_start = clock();
             game.draw(&renderer);
+            let draw_end = clock();
 
-            if cfg!(debug_assertions) {
-                unsafe {
-                    draw_frame_rate(&renderer, frame_time);
-                }
-            }
+            profiler::record(profiler::Sample {
+                frame_ms: frame_time,
+                update_ms: draw_start - update_start,
+                draw_ms: draw_end - draw_start,
+                steps,
+            });
+            profiler::draw(&renderer);
 
             let _ = browser::request_animation_frame(f.borrow().as_ref().unwrap());
         }));
@@ -172,30 +293,228 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
     Ok(image)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
-    pub x: i16,
-    pub y: i16,
+    pub x: i32,
+    pub y: i32,
 }
 
+const FRACTION_BITS: u32 = 8;
+
+// A number of pixels in 1/256ths. Fixed point rather than floats so the same
+// inputs always simulate the same run, whatever the machine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+
+    pub const fn from_int(pixels: i32) -> Self {
+        Fixed(pixels << FRACTION_BITS)
+    }
+
+    // Fixed::ratio(3, 5) is 0.6 of a pixel
+    pub const fn ratio(numerator: i32, denominator: i32) -> Self {
+        Fixed((numerator << FRACTION_BITS) / denominator)
+    }
+
+    // To the nearest pixel, halves rounding down the screen
+    pub fn round(self) -> i32 {
+        (self.0 + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS
+    }
+
+    pub fn floor(self) -> i32 {
+        self.0 >> FRACTION_BITS
+    }
+}
+
+impl From<i32> for Fixed {
+    fn from(pixels: i32) -> Self {
+        Fixed::from_int(pixels)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        self.0 -= other.0;
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, times: i32) -> Fixed {
+        Fixed(self.0 * times)
+    }
+}
+
+impl Div<i32> for Fixed {
+    type Output = Fixed;
+
+    fn div(self, divisor: i32) -> Fixed {
+        Fixed(self.0 / divisor)
+    }
+}
+
+// Positions and velocities for physics, only rounded to a Point to draw or collide
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Vector {
+    pub x: Fixed,
+    pub y: Fixed,
+}
+
+impl Vector {
+    pub fn round(self) -> Point {
+        Point {
+            x: self.x.round(),
+            y: self.y.round(),
+        }
+    }
+}
+
+impl From<Point> for Vector {
+    fn from(point: Point) -> Self {
+        Vector {
+            x: point.x.into(),
+            y: point.y.into(),
+        }
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl Mul<i32> for Vector {
+    type Output = Vector;
+
+    fn mul(self, times: i32) -> Vector {
+        Vector {
+            x: self.x * times,
+            y: self.y * times,
+        }
+    }
+}
+
+// Gamepad buttons, in the standard layout, and the keys they stand in for.
+// The face button both jumps and picks menu items.
+const GAMEPAD_KEYS: [(usize, &[&str]); 5] = [
+    (0, &["Space", "Enter"]),
+    (9, &["Escape"]),
+    (12, &["ArrowUp"]),
+    (13, &["ArrowDown"]),
+    (15, &["ArrowRight"]),
+];
+
+// The keyboard, along with the pointer and gamepad standing in for it
 #[derive(Debug)]
 pub struct KeyState {
-    pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+    pressed_keys: HashSet<String>,
+    just_pressed_keys: HashSet<String>,
+    pointer: Option<Point>,
+    click: Option<Point>,
+    gamepad: Vec<bool>,
 }
 
 impl KeyState {
     pub fn new() -> Self {
         KeyState {
-            pressed_keys: HashMap::new(),
+            pressed_keys: HashSet::new(),
+            just_pressed_keys: HashSet::new(),
+            pointer: None,
+            click: None,
+            gamepad: vec![],
         }
     }
 
     pub fn is_pressed(&self, code: &str) -> bool {
-        self.pressed_keys.contains_key(code)
+        self.pressed_keys.contains(code)
+    }
+
+    // True only for the first update after the key went down, ignoring key repeat
+    pub fn was_just_pressed(&self, code: &str) -> bool {
+        self.just_pressed_keys.contains(code)
+    }
+
+    // Where the pointer last was over the canvas
+    pub fn pointer(&self) -> Option<Point> {
+        self.pointer
     }
 
-    fn set_pressed(&mut self, code: &str, event: web_sys::KeyboardEvent) {
-        self.pressed_keys.insert(code.into(), event);
+    // Like `was_just_pressed`, where the canvas was clicked or tapped
+    pub fn was_just_clicked(&self) -> Option<Point> {
+        self.click
+    }
+
+    fn set_pressed(&mut self, code: &str) {
+        if !self.is_pressed(code) {
+            self.just_pressed_keys.insert(code.into());
+        }
+        self.pressed_keys.insert(code.into());
+    }
+
+    fn clear_just_pressed(&mut self) {
+        self.just_pressed_keys.clear();
+        self.click = None;
+    }
+
+    // Only changes are passed on, so a key held on the keyboard isn't let go
+    // of just because the gamepad's button for it is up
+    fn set_gamepad(&mut self, buttons: Vec<bool>) {
+        for (button, codes) in GAMEPAD_KEYS {
+            let was = self.gamepad.get(button).copied().unwrap_or(false);
+            let is = buttons.get(button).copied().unwrap_or(false);
+            if is && !was {
+                codes.iter().for_each(|code| self.set_pressed(code));
+            } else if was && !is {
+                codes.iter().for_each(|code| self.set_released(code));
+            }
+        }
+        self.gamepad = buttons;
     }
 
     fn set_released(&mut self, code: &str) {
@@ -207,6 +526,8 @@ fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
     let (keydown_sender, keyevent_receiver) = unbounded();
     let keydown_sender = Rc::new(RefCell::new(keydown_sender));
     let keyup_sender = Rc::clone(&keydown_sender);
+    let pointer_sender = Rc::clone(&keydown_sender);
+    let click_sender = Rc::clone(&keydown_sender);
 
     let onkeydown = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
         let _ = keydown_sender
@@ -220,12 +541,34 @@ fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
             .start_send(KeyPress::KeyUp(keycode));
     }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
 
+    let canvas = browser::canvas()?;
+    let pointer_canvas = canvas.clone();
+    let onmousemove = browser::closure_wrap(Box::new(move |event: web_sys::MouseEvent| {
+        let (x, y) = browser::canvas_position(&pointer_canvas, &event);
+        let _ = pointer_sender
+            .borrow_mut()
+            .start_send(KeyPress::PointerMove(Point { x, y }));
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>);
+
+    let click_canvas = canvas.clone();
+    let onmousedown = browser::closure_wrap(Box::new(move |event: web_sys::MouseEvent| {
+        let (x, y) = browser::canvas_position(&click_canvas, &event);
+        let _ = click_sender
+            .borrow_mut()
+            .start_send(KeyPress::PointerDown(Point { x, y }));
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>);
+
     browser::window()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
 
     browser::window()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
 
+    canvas.set_onmousemove(Some(onmousemove.as_ref().unchecked_ref()));
+    canvas.set_onmousedown(Some(onmousedown.as_ref().unchecked_ref()));
+
     onkeydown.forget();
     onkeyup.forget();
+    onmousemove.forget();
+    onmousedown.forget();
 
     Ok(keyevent_receiver)
 }
@@ -240,63 +583,122 @@ fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver
                     state.set_released(&event.code());
                 }
                 KeyPress::KeyDown(event) => {
-                    state.set_pressed(&event.code(), event);
+                    state.set_pressed(&event.code());
+                }
+                KeyPress::PointerMove(position) => {
+                    state.pointer = Some(position);
+                }
+                KeyPress::PointerDown(position) => {
+                    state.pointer = Some(position);
+                    state.click = Some(position);
                 }
             },
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Image {
     element: HtmlImageElement,
     bounding_box: Rect,
+    // Shared, every copy of an image has the same shape
+    mask: Option<Rc<CollisionMask>>,
 }
 
 impl Image {
     pub fn new(element: HtmlImageElement, position: Point) -> Self {
-        let bounding_box = Rect::new(position, element.width() as i16, element.height() as i16);
+        let bounding_box = Rect::new(position, element.width() as i32, element.height() as i32);
+        Self::sized(element, bounding_box)
+    }
 
+    // For when the size is already known, without asking the element
+    pub fn sized(element: HtmlImageElement, bounding_box: Rect) -> Self {
         Self {
             element,
             bounding_box,
+            mask: None,
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        renderer.draw_entire_image(&self.element, &self.bounding_box.position)
+    pub fn with_mask(mut self, mask: Rc<CollisionMask>) -> Self {
+        self.mask = Some(mask);
+        self
     }
 
-    pub fn _draw_bounding_box(&self, renderer: &Renderer) {
-        renderer._draw_rect(&self.bounding_box);
+    // Whether any solid part of the image is inside `rect`. The bounding box is
+    // checked first, and without a mask that is all there is to it.
+    pub fn touches(&self, rect: &Rect) -> bool {
+        rect.intersects(&self.bounding_box)
+            && match &self.mask {
+                Some(mask) => mask.overlaps(self.bounding_box.position, rect),
+                None => true,
+            }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        renderer.draw_entire_image(&self.element, &self.bounding_box.position)
     }
 
     pub fn bounding_box(&self) -> &Rect {
         &self.bounding_box
     }
 
-    pub fn move_horizontally(&mut self, distance: i16) {
+    pub fn move_horizontally(&mut self, distance: i32) {
         self.set_x(self.bounding_box.x() + distance);
     }
 
-    pub fn right(&self) -> i16 {
+    pub fn right(&self) -> i32 {
         self.bounding_box.right()
     }
 
-    pub fn set_x(&mut self, x: i16) {
+    pub fn set_x(&mut self, x: i32) {
         self.bounding_box.set_x(x);
     }
+
+    pub fn set_position(&mut self, position: Point) {
+        self.bounding_box.position = position;
+    }
 }
 
 pub struct SpriteSheet {
     pub sheet: Sheet,
     pub image: HtmlImageElement,
+    // By frame name. A cell without one collides as its whole box.
+    pub masks: HashMap<String, CollisionMask>,
 }
 
 impl SpriteSheet {
+    // Works out every cell's mask once, rather than each time one is checked
+    pub fn new(sheet: Sheet, image: HtmlImageElement) -> Result<Self> {
+        let masks = sheet
+            .frames
+            .iter()
+            .map(|(name, cell)| {
+                let source = Rect::new_from_x_y(
+                    cell.frame.x.into(),
+                    cell.frame.y.into(),
+                    cell.frame.w.into(),
+                    cell.frame.h.into(),
+                );
+                Ok((name.clone(), CollisionMask::from_image(&image, &source)?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(SpriteSheet {
+            sheet,
+            image,
+            masks,
+        })
+    }
+
     pub fn cell(&self, name: &str) -> Option<&Cell> {
         self.sheet.frames.get(name)
     }
 
+    pub fn mask(&self, name: &str) -> Option<&CollisionMask> {
+        self.masks.get(name)
+    }
+
     pub fn draw(&self, renderer: &Renderer, source: &Rect, destination: &Rect) {
         renderer.draw_image(&self.image, source, destination);
     }
@@ -305,12 +707,14 @@ impl SpriteSheet {
 #[derive(Clone)]
 pub struct Audio {
     context: AudioContext,
+    silent: bool,
 }
 
 impl Audio {
     pub fn new() -> Result<Self> {
         Ok(Audio {
             context: sound::create_audio_context()?,
+            silent: false,
         })
     }
 
@@ -323,11 +727,37 @@ impl Audio {
     }
 
     pub fn play_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::No)
+        if self.silent {
+            return Ok(());
+        }
+        sound::play_sound(
+            &self.context,
+            &sound.buffer,
+            sound::LOOPING::No,
+            &self.context.destination(),
+        )
     }
 
-    pub fn play_looping_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::Yes)
+    // Loops through its own volume control, so it can be turned down while it plays
+    pub fn play_looping_sound(&self, sound: &Sound) -> Result<Music> {
+        let gain = sound::create_gain(&self.context)?;
+        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::Yes, &gain)?;
+        Ok(Music {
+            gain,
+            volume: 1.0,
+            dimmed: false,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Audio {
+    // Outside a browser nothing can be played, so sound effects are skipped
+    pub fn silent() -> Self {
+        Audio {
+            context: AudioContext::from(JsValue::NULL),
+            silent: true,
+        }
     }
 }
 
@@ -336,24 +766,49 @@ pub struct Sound {
     pub buffer: AudioBuffer,
 }
 
-pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
-    let (mut click_sender, click_receiver) = unbounded();
-    let on_click = browser::closure_wrap(Box::new(move || {
-        click_sender.start_send(()).unwrap();
-    }) as Box<dyn FnMut()>);
-    elem.set_onclick(Some(on_click.as_ref().unchecked_ref()));
-    on_click.forget();
-    click_receiver
+// How much of its volume music keeps while dimmed, as it is under a menu
+const DIMMED_VOLUME: f32 = 0.3;
+
+pub struct Music {
+    gain: GainNode,
+    volume: f32,
+    dimmed: bool,
 }
 
+impl Music {
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.gain.gain().set_value(self.level());
+    }
+
+    pub fn set_dimmed(&mut self, dimmed: bool) {
+        self.dimmed = dimmed;
+        self.gain.gain().set_value(self.level());
+    }
+
+    // What's actually heard
+    fn level(&self) -> f32 {
+        if self.dimmed {
+            self.volume * DIMMED_VOLUME
+        } else {
+            self.volume
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct Rect {
     pub position: Point,
-    pub width: i16,
-    pub height: i16,
+    pub width: i32,
+    pub height: i32,
 }
 
 impl Rect {
-    pub fn new(position: Point, width: i16, height: i16) -> Self {
+    pub fn new(position: Point, width: i32, height: i32) -> Self {
         Rect {
             position,
             width,
@@ -361,54 +816,289 @@ impl Rect {
         }
     }
 
-    pub fn new_from_x_y(x: i16, y: i16, width: i16, height: i16) -> Self {
+    pub fn new_from_x_y(x: i32, y: i32, width: i32, height: i32) -> Self {
         let position = Point { x, y };
         Self::new(position, width, height)
     }
 
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x >= self.left()
+            && point.x < self.right()
+            && point.y >= self.top()
+            && point.y < self.bottom()
+    }
+
     pub fn intersects(&self, rect: &Rect) -> bool {
         let x_overlaps = self.left() < rect.right() && self.right() > rect.left();
         let y_overlaps = self.top() < rect.bottom() && self.bottom() > rect.top();
         x_overlaps && y_overlaps
     }
 
-    pub fn left(&self) -> i16 {
+    pub fn left(&self) -> i32 {
         self.x()
     }
 
-    pub fn right(&self) -> i16 {
+    pub fn right(&self) -> i32 {
         self.x() + self.width
     }
 
-    pub fn bottom(&self) -> i16 {
+    pub fn bottom(&self) -> i32 {
         self.y() + self.height
     }
 
-    pub fn top(&self) -> i16 {
+    pub fn top(&self) -> i32 {
         self.y()
     }
 
-    pub fn x(&self) -> i16 {
+    pub fn x(&self) -> i32 {
         self.position.x
     }
 
-    pub fn y(&self) -> i16 {
+    pub fn y(&self) -> i32 {
         self.position.y
     }
 
-    pub fn width(&self) -> i16 {
+    pub fn width(&self) -> i32 {
         self.width
     }
 
-    pub fn height(&self) -> i16 {
+    pub fn height(&self) -> i32 {
         self.height
     }
 
-    pub fn set_x(&mut self, x: i16) {
+    pub fn center(&self) -> Point {
+        Point {
+            x: self.x() + self.width / 2,
+            y: self.y() + self.height / 2,
+        }
+    }
+
+    pub fn set_x(&mut self, x: i32) {
         self.position.x = x;
     }
 }
 
+// Which way the face that was hit looks, Up being the top of a platform
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normal {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Impact {
+    // How far through the tick the boxes first touched, from 0 to 1
+    pub time: Fixed,
+    pub normal: Normal,
+}
+
+// A box moving in a straight line over one tick. Checking the whole path
+// rather than where it ends up means nothing thin can be skipped over.
+#[derive(Clone, Copy, Debug)]
+pub struct Sweep {
+    pub from: Rect,
+    pub motion: Point,
+}
+
+impl Sweep {
+    pub fn ending_at(to: Rect, motion: Point) -> Self {
+        Sweep {
+            from: Rect::new_from_x_y(to.x() - motion.x, to.y() - motion.y, to.width, to.height),
+            motion,
+        }
+    }
+
+    pub fn to(&self) -> Rect {
+        Rect::new_from_x_y(
+            self.from.x() + self.motion.x,
+            self.from.y() + self.motion.y,
+            self.from.width,
+            self.from.height,
+        )
+    }
+
+    // Everywhere the box passes through
+    pub fn bounds(&self) -> Rect {
+        let to = self.to();
+        let left = self.from.left().min(to.left());
+        let top = self.from.top().min(to.top());
+        Rect::new_from_x_y(
+            left,
+            top,
+            self.from.right().max(to.right()) - left,
+            self.from.bottom().max(to.bottom()) - top,
+        )
+    }
+
+    pub fn at(rect: Rect) -> Self {
+        Sweep {
+            from: rect,
+            motion: Point { x: 0, y: 0 },
+        }
+    }
+
+    // The same movement seen from something that moved by `motion` itself
+    pub fn relative_to(&self, motion: Point) -> Self {
+        Sweep {
+            from: Rect::new_from_x_y(
+                self.from.x() + motion.x,
+                self.from.y() + motion.y,
+                self.from.width,
+                self.from.height,
+            ),
+            motion: Point {
+                x: self.motion.x - motion.x,
+                y: self.motion.y - motion.y,
+            },
+        }
+    }
+
+    pub fn impact(&self, other: &Rect) -> Option<Impact> {
+        // Something that isn't moving only touches what it already overlaps
+        if self.motion == (Point { x: 0, y: 0 }) {
+            return self.from.intersects(other).then(|| self.overlap(other));
+        }
+
+        let x = Span::along(
+            (self.from.left(), self.from.right()),
+            (other.left(), other.right()),
+            self.motion.x,
+            (Normal::Left, Normal::Right),
+        )?;
+        let y = Span::along(
+            (self.from.top(), self.from.bottom()),
+            (other.top(), other.bottom()),
+            self.motion.y,
+            (Normal::Up, Normal::Down),
+        )?;
+
+        // Whichever axis starts overlapping last is the face that was hit,
+        // a tie goes to landing on top
+        let (entry, normal) = match (x.entry, y.entry) {
+            (Some(x_entry), Some(y_entry)) if y_entry.before(&x_entry) => (x_entry, x.normal),
+            (Some(x_entry), None) => (x_entry, x.normal),
+            (_, Some(y_entry)) => (y_entry, y.normal),
+            (None, None) => unreachable!("Only a box that isn't moving is always overlapping"),
+        };
+        // Overlapping for the whole tick is the same as until its end here
+        let x_exit = x.exit.unwrap_or(Time::END);
+        let y_exit = y.exit.unwrap_or(Time::END);
+        let exit = if x_exit.before(&y_exit) {
+            x_exit
+        } else {
+            y_exit
+        };
+
+        if !entry.before(&exit) || !entry.before(&Time::END) || !Time::START.before(&exit) {
+            return None;
+        }
+
+        Some(Impact {
+            time: Fixed::ratio(entry.distance.max(0), entry.speed),
+            normal,
+        })
+    }
+
+    // Neither box is moving relative to the other, so the face is the one
+    // that is least overlapped
+    fn overlap(&self, other: &Rect) -> Impact {
+        let center = self.from.center();
+        let other_center = other.center();
+        let overlap_x = self.from.right().min(other.right()) - self.from.left().max(other.left());
+        let overlap_y = self.from.bottom().min(other.bottom()) - self.from.top().max(other.top());
+
+        let normal = if overlap_x < overlap_y {
+            if center.x < other_center.x {
+                Normal::Left
+            } else {
+                Normal::Right
+            }
+        } else if center.y < other_center.y {
+            Normal::Up
+        } else {
+            Normal::Down
+        };
+
+        Impact {
+            time: Fixed::ZERO,
+            normal,
+        }
+    }
+}
+
+// A fraction of a tick, kept exact so that ties are really ties
+#[derive(Clone, Copy)]
+struct Time {
+    distance: i32,
+    speed: i32,
+}
+
+impl Time {
+    const START: Time = Time {
+        distance: 0,
+        speed: 1,
+    };
+    const END: Time = Time {
+        distance: 1,
+        speed: 1,
+    };
+
+    fn before(&self, other: &Time) -> bool {
+        i64::from(self.distance) * i64::from(other.speed)
+            < i64::from(other.distance) * i64::from(self.speed)
+    }
+}
+
+// When the boxes start and stop overlapping along one axis. No entry or exit
+// means they overlap along it the whole time.
+struct Span {
+    entry: Option<Time>,
+    exit: Option<Time>,
+    normal: Normal,
+}
+
+impl Span {
+    // `faces` are the normals for moving forwards and backwards along the axis
+    fn along(
+        moving: (i32, i32),
+        other: (i32, i32),
+        speed: i32,
+        faces: (Normal, Normal),
+    ) -> Option<Self> {
+        let ((start, end), (other_start, other_end)) = (moving, other);
+        let time = |distance| {
+            Some(Time {
+                distance,
+                speed: speed.abs(),
+            })
+        };
+
+        if speed > 0 {
+            Some(Span {
+                entry: time(other_start - end),
+                exit: time(other_end - start),
+                normal: faces.0,
+            })
+        } else if speed < 0 {
+            Some(Span {
+                entry: time(start - other_end),
+                exit: time(end - other_start),
+                normal: faces.1,
+            })
+        } else if start < other_end && end > other_start {
+            Some(Span {
+                entry: None,
+                exit: None,
+                normal: faces.0,
+            })
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -429,26 +1119,178 @@ mod tests {
 
         assert!(rect2.intersects(&rect1));
     }
-}
 
-unsafe fn draw_frame_rate(renderer: &Renderer, frame_time: f64) {
-    static mut FRAMES_COUNTED: i32 = 0;
-    static mut TOTAL_FRAME_TIME: f64 = 0.0;
-    static mut FRAME_RATE: i32 = 0;
+    #[test]
+    fn rects_past_the_old_sixteen_bit_range_still_collide() {
+        let rect1 = Rect::new_from_x_y(40_000, 33_000, 100, 100);
+        let rect2 = Rect::new_from_x_y(40_050, 33_050, 100, 100);
+
+        assert_eq!(rect1.right(), 40_100);
+        assert_eq!(rect1.bottom(), 33_100);
+        assert!(rect1.intersects(&rect2));
+    }
+
+    // Nothing in the world gets far from the screen, but the maths has to
+    // hold wherever things are
+    const FAR: i32 = i32::MAX - 10_000;
+
+    fn moved(rect: Rect, by: i32) -> Rect {
+        Rect::new_from_x_y(rect.x() + by, rect.y() + by, rect.width, rect.height)
+    }
+
+    #[test]
+    fn rects_far_from_the_origin_measure_and_collide() {
+        let rect = Rect::new_from_x_y(FAR, FAR, 1000, 1000);
+
+        assert_eq!(rect.right(), FAR + 1000);
+        assert_eq!(rect.bottom(), FAR + 1000);
+        assert_eq!(
+            rect.center(),
+            Point {
+                x: FAR + 500,
+                y: FAR + 500
+            }
+        );
+        assert!(rect.contains(&Point {
+            x: FAR + 999,
+            y: FAR
+        }));
+        assert!(rect.intersects(&Rect::new_from_x_y(FAR + 999, FAR + 999, 1000, 1000)));
+        assert!(!rect.intersects(&Rect::new_from_x_y(FAR + 1000, FAR, 1000, 1000)));
+
+        let behind = Rect::new_from_x_y(-FAR, -FAR, 1000, 1000);
+        assert_eq!(behind.right(), -FAR + 1000);
+        assert!(!behind.intersects(&rect));
+    }
+
+    #[test]
+    fn images_far_from_the_origin_move_and_touch() {
+        let solid = vec![255; 100 * 50 * 4];
+        let mut image = Image::sized(
+            HtmlImageElement::from(JsValue::NULL),
+            Rect::new_from_x_y(FAR, FAR, 100, 50),
+        )
+        .with_mask(Rc::new(CollisionMask::from_alpha(100, 50, &solid)));
+
+        image.move_horizontally(-5);
+
+        assert_eq!(image.right(), FAR + 95);
+        assert!(image.touches(&Rect::new_from_x_y(FAR + 90, FAR + 40, 10, 10)));
+        assert!(!image.touches(&Rect::new_from_x_y(FAR + 95, FAR + 40, 10, 10)));
+    }
+
+    #[test]
+    fn sweeps_far_from_the_origin_find_the_same_impacts() {
+        let falling = Sweep {
+            from: Rect::new_from_x_y(0, 0, 10, 10),
+            motion: Point { x: 0, y: 100 },
+        };
+        let thin = Rect::new_from_x_y(0, 50, 10, 2);
+        let running = Sweep {
+            from: Rect::new_from_x_y(0, 0, 10, 10),
+            motion: Point { x: 20, y: 1 },
+        };
+        let wall = Rect::new_from_x_y(25, 0, 10, 10);
+
+        [FAR - 1000, -FAR].iter().for_each(|&by| {
+            let far = |sweep: Sweep| Sweep {
+                from: moved(sweep.from, by),
+                ..sweep
+            };
+            assert_eq!(far(falling).impact(&moved(thin, by)), falling.impact(&thin));
+            assert_eq!(far(running).impact(&moved(wall, by)), running.impact(&wall));
+            assert_eq!(far(running).bounds().right(), by + 30);
+            assert_eq!(far(running).to().x(), by + 20);
+        });
+    }
+
+    #[test]
+    fn a_fast_box_cannot_pass_through_a_thin_one() {
+        let sweep = Sweep {
+            from: Rect::new_from_x_y(0, 0, 10, 10),
+            motion: Point { x: 0, y: 100 },
+        };
+        let thin = Rect::new_from_x_y(0, 50, 10, 2);
+
+        assert!(!Rect::new_from_x_y(0, 100, 10, 10).intersects(&thin));
+        assert_eq!(
+            sweep.impact(&thin),
+            Some(Impact {
+                time: Fixed::ratio(40, 100),
+                normal: Normal::Up
+            })
+        );
+    }
 
-    FRAMES_COUNTED += 1;
-    TOTAL_FRAME_TIME += frame_time;
+    #[test]
+    fn moving_sideways_into_a_box_hits_its_side() {
+        let sweep = Sweep {
+            from: Rect::new_from_x_y(0, 0, 10, 10),
+            motion: Point { x: 20, y: 1 },
+        };
 
-    if TOTAL_FRAME_TIME > 1000.0 {
-        FRAME_RATE = FRAMES_COUNTED;
-        TOTAL_FRAME_TIME = 0.0;
-        FRAMES_COUNTED = 0;
+        let impact = sweep.impact(&Rect::new_from_x_y(25, 0, 10, 10));
+        assert_eq!(impact.map(|impact| impact.normal), Some(Normal::Left));
     }
 
-    if let Err(err) = renderer.draw_text(
-        &format!("Frame Rate {}", FRAME_RATE),
-        &Point { x: 400, y: 100 },
-    ) {
-        error!("Could not draw text {:#?}", err);
+    #[test]
+    fn only_reaching_a_box_is_not_touching_it() {
+        let sweep = Sweep::ending_at(Rect::new_from_x_y(0, 10, 10, 10), Point { x: 0, y: 10 });
+
+        assert!(sweep.impact(&Rect::new_from_x_y(0, 20, 10, 10)).is_none());
+        assert!(sweep.impact(&Rect::new_from_x_y(0, 19, 10, 10)).is_some());
+    }
+
+    #[test]
+    fn hitting_a_corner_exactly_lands_on_top() {
+        let sweep = Sweep {
+            from: Rect::new_from_x_y(0, 0, 10, 10),
+            motion: Point { x: 10, y: 10 },
+        };
+
+        let impact = sweep.impact(&Rect::new_from_x_y(15, 15, 10, 10));
+        assert_eq!(impact.map(|impact| impact.normal), Some(Normal::Up));
+    }
+
+    #[test]
+    fn fractions_add_up_exactly() {
+        let tenths = (0..10).fold(Fixed::ZERO, |total, _| total + Fixed::ratio(1, 10));
+        assert_eq!(tenths.round(), 1);
+
+        let steps = (0..5).fold(Fixed::ZERO, |total, _| total + Fixed::ratio(3, 5));
+        assert_eq!(steps.floor(), 2);
+        assert_eq!(steps.round(), 3);
+    }
+
+    #[test]
+    fn vectors_round_to_the_nearest_point() {
+        let vector = Vector {
+            x: Fixed::ratio(5, 2),
+            y: -Fixed::ratio(7, 4),
+        };
+
+        assert_eq!(vector.round(), Point { x: 3, y: -2 });
+        assert_eq!(
+            Vector::from(Point { x: 4, y: -9 }).round(),
+            Point { x: 4, y: -9 }
+        );
+    }
+
+    #[test]
+    fn gamepad_buttons_press_and_release_their_keys() {
+        let mut keystate = KeyState::new();
+        keystate.set_pressed("Escape");
+
+        keystate.set_gamepad(vec![true]);
+        assert!(keystate.was_just_pressed("Space"));
+        assert!(keystate.is_pressed("Enter"));
+
+        keystate.clear_just_pressed();
+        keystate.set_gamepad(vec![true]);
+        assert!(!keystate.was_just_pressed("Space"));
+
+        keystate.set_gamepad(vec![]);
+        assert!(!keystate.is_pressed("Space"));
+        assert!(keystate.is_pressed("Escape"));
     }
 }
@@ -0,0 +1,116 @@
+use super::Rect;
+
+// Bits for what something is, or for what it collides with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layers(pub u32);
+
+impl Layers {
+    pub const fn with(self, other: Layers) -> Layers {
+        Layers(self.0 | other.0)
+    }
+
+    pub fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+struct Entry {
+    left: i32,
+    right: i32,
+    layers: Layers,
+    index: usize,
+}
+
+// Sort and sweep along x, which is the only direction a side scroller
+// spreads out in. Everything is kept sorted by its left edge, so finding what
+// spans a stretch of x is a binary search rather than a look at everything.
+#[derive(Default)]
+pub struct BroadPhase {
+    entries: Vec<Entry>,
+    widest: i32,
+}
+
+impl BroadPhase {
+    // Each item is found again by its position in `bounds`
+    pub fn rebuild(&mut self, bounds: impl Iterator<Item = (Rect, Layers)>) {
+        self.entries.clear();
+        self.entries
+            .extend(bounds.enumerate().map(|(index, (bounds, layers))| Entry {
+                left: bounds.left(),
+                right: bounds.right(),
+                layers,
+                index,
+            }));
+        self.entries.sort_by_key(|entry| entry.left);
+        self.widest = self
+            .entries
+            .iter()
+            .map(|entry| entry.right - entry.left)
+            .max()
+            .unwrap_or(0);
+    }
+
+    // Everything on any of `layers` that overlaps `left..right` along x, in
+    // the order it was added
+    pub fn query(&self, left: i32, right: i32, layers: Layers) -> Vec<usize> {
+        // Nothing starting this far back can reach `left`
+        let first = self
+            .entries
+            .partition_point(|entry| entry.left <= left - self.widest);
+        let last = self.entries.partition_point(|entry| entry.left < right);
+
+        let mut found: Vec<usize> = self.entries[first..last.max(first)]
+            .iter()
+            .filter(|entry| entry.right > left && entry.layers.intersects(layers))
+            .map(|entry| entry.index)
+            .collect();
+        found.sort_unstable();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUND: Layers = Layers(1);
+    const COIN: Layers = Layers(1 << 1);
+
+    fn spans(spans: &[(i32, i32, Layers)]) -> BroadPhase {
+        let mut broad_phase = BroadPhase::default();
+        broad_phase.rebuild(
+            spans.iter().map(|&(left, right, layers)| {
+                (Rect::new_from_x_y(left, 0, right - left, 10), layers)
+            }),
+        );
+        broad_phase
+    }
+
+    #[test]
+    fn only_what_overlaps_the_range_is_found() {
+        let broad_phase = spans(&[
+            (500, 600, GROUND),
+            (0, 400, GROUND),
+            (300, 310, COIN),
+            (390, 400, COIN),
+        ]);
+
+        assert_eq!(broad_phase.query(350, 360, GROUND.with(COIN)), vec![1]);
+        assert_eq!(
+            broad_phase.query(305, 395, GROUND.with(COIN)),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            broad_phase.query(400, 500, GROUND.with(COIN)),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn other_layers_are_left_out() {
+        let broad_phase = spans(&[(0, 400, GROUND), (300, 310, COIN)]);
+
+        assert_eq!(broad_phase.query(300, 310, COIN), vec![1]);
+        assert_eq!(broad_phase.query(300, 310, Layers(0)), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use web_sys::HtmlImageElement;
+
+use super::{Point, Rect};
+use crate::browser;
+
+// Anything at least half opaque is solid, so soft antialiased edges aren't
+const ALPHA_THRESHOLD: u8 = 128;
+const BITS: usize = 32;
+
+// Which pixels of a sprite are solid, one bit each row by row. It can be
+// serialized so that masks can be worked out ahead of time instead of at load.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CollisionMask {
+    width: i32,
+    height: i32,
+    bits: Vec<u32>,
+}
+
+impl CollisionMask {
+    pub fn from_image(image: &HtmlImageElement, source: &Rect) -> Result<Self> {
+        let pixels = browser::image_pixels(
+            image,
+            source.x().into(),
+            source.y().into(),
+            source.width() as u32,
+            source.height() as u32,
+        )?;
+        Ok(CollisionMask::from_alpha(
+            source.width(),
+            source.height(),
+            &pixels,
+        ))
+    }
+
+    // `pixels` are RGBA, the way a canvas hands them back
+    pub fn from_alpha(width: i32, height: i32, pixels: &[u8]) -> Self {
+        let mut bits = vec![0; (width * height) as usize / BITS + 1];
+        pixels
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[3] >= ALPHA_THRESHOLD)
+            .for_each(|(index, _)| bits[index / BITS] |= 1 << (index % BITS));
+
+        CollisionMask {
+            width,
+            height,
+            bits,
+        }
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return false;
+        }
+        let index = (y * self.width + x) as usize;
+        self.bits[index / BITS] & (1 << (index % BITS)) != 0
+    }
+
+    // Whether any solid pixel of the mask, drawn at `position`, is inside `rect`
+    pub fn overlaps(&self, position: Point, rect: &Rect) -> bool {
+        let left = (rect.left() - position.x).max(0);
+        let right = (rect.right() - position.x).min(self.width);
+        let top = (rect.top() - position.y).max(0);
+        let bottom = (rect.bottom() - position.y).min(self.height);
+
+        (top..bottom).any(|y| (left..right).any(|x| self.is_solid(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Point = Point { x: 0, y: 0 };
+
+    // A 3x3 plus sign, the corners are see through
+    fn plus() -> CollisionMask {
+        let alpha = [0, 255, 0, 255, 255, 255, 0, 255, 0];
+        let pixels: Vec<u8> = alpha.iter().flat_map(|&a| vec![0, 0, 0, a]).collect();
+        CollisionMask::from_alpha(3, 3, &pixels)
+    }
+
+    #[test]
+    fn see_through_pixels_are_not_solid() {
+        let mask = plus();
+
+        assert!(mask.is_solid(1, 1));
+        assert!(mask.is_solid(0, 1));
+        assert!(!mask.is_solid(0, 0));
+        assert!(!mask.is_solid(2, 2));
+        assert!(!mask.is_solid(3, 1));
+    }
+
+    #[test]
+    fn a_rect_over_a_see_through_corner_misses() {
+        let mask = plus();
+
+        assert!(!mask.overlaps(ORIGIN, &Rect::new_from_x_y(-5, -5, 6, 6)));
+        assert!(mask.overlaps(ORIGIN, &Rect::new_from_x_y(-5, -5, 7, 7)));
+        assert!(mask.overlaps(Point { x: 100, y: 50 }, &Rect::new_from_x_y(101, 40, 1, 11)));
+    }
+}
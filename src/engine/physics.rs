@@ -0,0 +1,194 @@
+// Just enough physics for a rope. Everything is simulated in fractional
+// pixels and converted to and from the integer Points the rest of the engine uses.
+use super::Point;
+
+const GRAVITY: f32 = 0.5;
+const DAMPING: f32 = 0.98;
+// More iterations make the rope stiffer
+const ITERATIONS: usize = 20;
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    x: f32,
+    y: f32,
+    previous_x: f32,
+    previous_y: f32,
+}
+
+impl Particle {
+    fn at(x: f32, y: f32) -> Self {
+        Particle {
+            x,
+            y,
+            previous_x: x,
+            previous_y: y,
+        }
+    }
+
+    fn pin(&mut self, point: Point) {
+        *self = Particle::at(point.x as f32, point.y as f32);
+    }
+
+    // Verlet integration, the velocity is whatever the last step moved it by
+    fn step(&mut self) {
+        let velocity_x = (self.x - self.previous_x) * DAMPING;
+        let velocity_y = (self.y - self.previous_y) * DAMPING;
+        self.previous_x = self.x;
+        self.previous_y = self.y;
+        self.x += velocity_x;
+        self.y += velocity_y + GRAVITY;
+    }
+
+    fn point(&self) -> Point {
+        Point {
+            x: self.x.round() as i32,
+            y: self.y.round() as i32,
+        }
+    }
+}
+
+// A chain of particles with both ends held, hanging under gravity
+pub struct Rope {
+    particles: Vec<Particle>,
+    link_length: f32,
+}
+
+impl Rope {
+    pub fn new(start: Point, end: Point, links: usize, length: i32) -> Self {
+        let links = links.max(1);
+        let particles = (0..=links)
+            .map(|index| {
+                let along = index as f32 / links as f32;
+                Particle::at(
+                    start.x as f32 + (end.x - start.x) as f32 * along,
+                    start.y as f32 + (end.y - start.y) as f32 * along,
+                )
+            })
+            .collect();
+
+        Rope {
+            particles,
+            link_length: length as f32 / links as f32,
+        }
+    }
+
+    // Moves the ends to `start` and `end` and lets the rest of the rope follow,
+    // never letting it hang below `floor`
+    pub fn update(&mut self, start: Point, end: Point, floor: i32) {
+        let last = self.particles.len() - 1;
+        self.particles[1..last].iter_mut().for_each(Particle::step);
+
+        for _ in 0..ITERATIONS {
+            self.particles[0].pin(start);
+            self.particles[last].pin(end);
+
+            for index in 0..last {
+                self.tighten(index, index + 1);
+            }
+
+            self.particles[1..last]
+                .iter_mut()
+                .for_each(|particle| particle.y = particle.y.min(floor as f32));
+        }
+
+        self.particles[0].pin(start);
+        self.particles[last].pin(end);
+    }
+
+    // A rope only pulls, so links that are slack are left alone
+    fn tighten(&mut self, first: usize, second: usize) {
+        let (a, b) = (self.particles[first], self.particles[second]);
+        let delta_x = b.x - a.x;
+        let delta_y = b.y - a.y;
+        let distance = (delta_x * delta_x + delta_y * delta_y).sqrt();
+        if distance <= self.link_length || distance == 0.0 {
+            return;
+        }
+
+        let correction = (distance - self.link_length) / distance / 2.0;
+        self.particles[first].x += delta_x * correction;
+        self.particles[first].y += delta_y * correction;
+        self.particles[second].x -= delta_x * correction;
+        self.particles[second].y -= delta_y * correction;
+    }
+
+    pub fn points(&self) -> Vec<Point> {
+        self.particles.iter().map(Particle::point).collect()
+    }
+}
+
+// Where `point` has to be, moving only up or down, to be no further than
+// `reach` from `anchor`
+pub fn limit_vertically(anchor: Point, point: Point, reach: i32) -> Point {
+    let delta_x = (point.x - anchor.x) as f32;
+    let delta_y = (point.y - anchor.y) as f32;
+    let reach = reach as f32;
+    if delta_x * delta_x + delta_y * delta_y <= reach * reach {
+        return point;
+    }
+
+    let vertical_reach = (reach * reach - delta_x * delta_x).max(0.0).sqrt();
+    Point {
+        x: point.x,
+        y: anchor.y + (vertical_reach.copysign(delta_y)) as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: Point = Point { x: 0, y: 100 };
+    const END: Point = Point { x: 100, y: 100 };
+
+    fn settled_rope(length: i32) -> Rope {
+        let mut rope = Rope::new(START, END, 10, length);
+        (0..500).for_each(|_| rope.update(START, END, 1000));
+        rope
+    }
+
+    #[test]
+    fn a_slack_rope_sags_between_its_ends() {
+        let rope = settled_rope(150);
+
+        let points = rope.points();
+        let middle = points[points.len() / 2];
+
+        assert!(middle.y > START.y + 20, "{:?}", points);
+        assert_eq!(points[0], START);
+        assert_eq!(points[points.len() - 1], END);
+    }
+
+    #[test]
+    fn a_rope_does_not_stretch_much_under_its_own_weight() {
+        let points = settled_rope(150).points();
+
+        let length: f32 = points
+            .windows(2)
+            .map(|pair| ((pair[1].x - pair[0].x) as f32).hypot((pair[1].y - pair[0].y) as f32))
+            .sum();
+
+        assert!(length < 150.0 * 1.05, "{}", length);
+    }
+
+    #[test]
+    fn a_rope_rests_on_the_floor() {
+        let mut rope = Rope::new(START, END, 10, 400);
+        (0..500).for_each(|_| rope.update(START, END, 120));
+
+        assert!(rope.points().iter().all(|point| point.y <= 120));
+    }
+
+    #[test]
+    fn limiting_only_moves_points_that_are_out_of_reach() {
+        let anchor = Point { x: 0, y: 0 };
+
+        let near = limit_vertically(anchor, Point { x: 30, y: 40 }, 50);
+        let below = limit_vertically(anchor, Point { x: 30, y: 200 }, 50);
+        let above = limit_vertically(anchor, Point { x: 30, y: -200 }, 50);
+
+        assert_eq!(near, Point { x: 30, y: 40 });
+        assert_eq!(below, Point { x: 30, y: 40 });
+        assert_eq!(above, Point { x: 30, y: -40 });
+    }
+}
@@ -0,0 +1,227 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use serde::Serialize;
+
+use super::{Point, Rect, Renderer};
+
+// About two seconds of frames at 60 frames a second
+const HISTORY: usize = 120;
+const BUCKET_MS: f64 = 4.0;
+// The last bucket holds every frame slower than the rest
+const BUCKETS: usize = 10;
+const FRAME_BUDGET_MS: f64 = 1000.0 / 60.0;
+const TEXT_POSITION: Point = Point { x: 400, y: 100 };
+const GRAPH_LEFT: i32 = 400;
+const GRAPH_BOTTOM: i32 = 180;
+const BAR_WIDTH: i32 = 2;
+const PIXELS_PER_MS: f64 = 2.0;
+const BAR_COLOR: &str = "#00CC66";
+const SLOW_BAR_COLOR: &str = "#FF3333";
+const BUDGET_COLOR: &str = "#FFFFFF";
+
+// What one animation frame cost
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Sample {
+    // Since the frame before
+    pub frame_ms: f64,
+    pub update_ms: f64,
+    pub draw_ms: f64,
+    // Fixed updates run to catch up, none when the display outpaces 60 Hz
+    pub steps: u32,
+}
+
+// Averages over the recent frames, and how their frame times spread out
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub fps: f64,
+    pub frame_ms: f64,
+    pub slowest_frame_ms: f64,
+    pub update_ms: f64,
+    pub draw_ms: f64,
+    pub steps: f64,
+    pub bucket_ms: f64,
+    pub histogram: [u32; BUCKETS],
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    samples: VecDeque<Sample>,
+    histogram: [u32; BUCKETS],
+    // Measuring goes on either way, only the overlay is hidden
+    visible: bool,
+}
+
+impl Profiler {
+    pub fn record(&mut self, sample: Sample) {
+        self.samples.push_back(sample);
+        self.histogram[bucket(sample.frame_ms)] += 1;
+
+        if self.samples.len() > HISTORY {
+            if let Some(oldest) = self.samples.pop_front() {
+                self.histogram[bucket(oldest.frame_ms)] -= 1;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let count = self.samples.len().max(1) as f64;
+        let average =
+            |measure: fn(&Sample) -> f64| self.samples.iter().map(measure).sum::<f64>() / count;
+        let frame_ms = average(|sample| sample.frame_ms);
+
+        Stats {
+            fps: if frame_ms > 0.0 {
+                1000.0 / frame_ms
+            } else {
+                0.0
+            },
+            frame_ms,
+            slowest_frame_ms: self
+                .samples
+                .iter()
+                .map(|sample| sample.frame_ms)
+                .fold(0.0, f64::max),
+            update_ms: average(|sample| sample.update_ms),
+            draw_ms: average(|sample| sample.draw_ms),
+            steps: average(|sample| sample.steps.into()),
+            bucket_ms: BUCKET_MS,
+            histogram: self.histogram,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // The numbers, and a bar for each recent frame against the 60 Hz budget
+    pub fn draw(&self, renderer: &Renderer) {
+        if !self.visible {
+            return;
+        }
+        let stats = self.stats();
+        let text = format!(
+            "FPS {:.0} update {:.2}ms draw {:.2}ms steps {:.2}",
+            stats.fps, stats.update_ms, stats.draw_ms, stats.steps
+        );
+        if let Err(err) = renderer.draw_text(&text, &TEXT_POSITION) {
+            error!("Could not draw text {:#?}", err);
+        }
+
+        self.samples.iter().enumerate().for_each(|(index, sample)| {
+            let height = (sample.frame_ms * PIXELS_PER_MS) as i32;
+            let color = if sample.frame_ms > FRAME_BUDGET_MS {
+                SLOW_BAR_COLOR
+            } else {
+                BAR_COLOR
+            };
+            renderer.fill_rect(
+                &Rect::new_from_x_y(
+                    GRAPH_LEFT + index as i32 * BAR_WIDTH,
+                    GRAPH_BOTTOM - height,
+                    BAR_WIDTH,
+                    height,
+                ),
+                color,
+            );
+        });
+
+        let budget = GRAPH_BOTTOM - (FRAME_BUDGET_MS * PIXELS_PER_MS) as i32;
+        renderer.stroke_path(
+            &[
+                Point {
+                    x: GRAPH_LEFT,
+                    y: budget,
+                },
+                Point {
+                    x: GRAPH_LEFT + HISTORY as i32 * BAR_WIDTH,
+                    y: budget,
+                },
+            ],
+            BUDGET_COLOR,
+        );
+    }
+}
+
+fn bucket(frame_ms: f64) -> usize {
+    ((frame_ms.max(0.0) / BUCKET_MS) as usize).min(BUCKETS - 1)
+}
+
+thread_local! {
+    // There's only ever the one game loop, and JS asks about it from outside
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::default());
+}
+
+pub fn record(sample: Sample) {
+    PROFILER.with(|profiler| profiler.borrow_mut().record(sample));
+}
+
+pub fn stats() -> Stats {
+    PROFILER.with(|profiler| profiler.borrow().stats())
+}
+
+pub fn toggle() {
+    PROFILER.with(|profiler| profiler.borrow_mut().toggle());
+}
+
+pub fn draw(renderer: &Renderer) {
+    PROFILER.with(|profiler| profiler.borrow().draw(renderer));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(frame_ms: f64) -> Sample {
+        Sample {
+            frame_ms,
+            update_ms: 1.0,
+            draw_ms: 2.0,
+            steps: 1,
+        }
+    }
+
+    #[test]
+    fn frames_are_averaged() {
+        let mut profiler = Profiler::default();
+        profiler.record(frame(10.0));
+        profiler.record(Sample {
+            steps: 3,
+            ..frame(30.0)
+        });
+
+        let stats = profiler.stats();
+        assert_eq!(stats.fps, 50.0);
+        assert_eq!(stats.slowest_frame_ms, 30.0);
+        assert_eq!(stats.update_ms, 1.0);
+        assert_eq!(stats.steps, 2.0);
+    }
+
+    #[test]
+    fn the_overlay_starts_hidden() {
+        let mut profiler = Profiler::default();
+        assert!(!profiler.visible);
+
+        profiler.toggle();
+        assert!(profiler.visible);
+    }
+
+    #[test]
+    fn old_frames_roll_out_of_the_histogram() {
+        let mut profiler = Profiler::default();
+        profiler.record(frame(100.0));
+        (0..HISTORY).for_each(|_| profiler.record(frame(FRAME_BUDGET_MS)));
+
+        let histogram = profiler.stats().histogram;
+        assert_eq!(histogram[BUCKETS - 1], 0);
+        assert_eq!(histogram[bucket(FRAME_BUDGET_MS)], HISTORY as u32);
+        assert_eq!(histogram.iter().sum::<u32>(), HISTORY as u32);
+    }
+
+    #[test]
+    fn nothing_recorded_is_not_a_division_by_zero() {
+        let stats = Profiler::default().stats();
+
+        assert_eq!(stats.fps, 0.0);
+        assert_eq!(stats.histogram, [0; BUCKETS]);
+    }
+}
@@ -0,0 +1,258 @@
+use super::{KeyState, Renderer};
+
+// What the scene on top wants to happen to the stack after its update
+pub enum Transition<C> {
+    Stay,
+    // Covers the current scene, which waits underneath until it's popped off
+    Push(Box<dyn Scene<C>>),
+    Pop,
+    Replace(Box<dyn Scene<C>>),
+    // Takes everything off the stack to start over from the one scene
+    Clear(Box<dyn Scene<C>>),
+}
+
+// One screen of a game. Every scene shares the stack's context `C`, so a
+// scene only holds what's its own and state carries over between scenes.
+pub trait Scene<C> {
+    fn update(&mut self, context: &mut C, keystate: &KeyState) -> Transition<C>;
+    fn draw(&self, context: &C, renderer: &Renderer);
+
+    // Called as the scene goes onto the stack, and as it comes off
+    fn enter(&mut self, _context: &mut C) {}
+    fn exit(&mut self, _context: &mut C) {}
+
+    // An overlay is drawn over whatever is under it, which stays frozen
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+// Only the scene on top updates, but overlays let the ones below show through
+pub struct SceneStack<C> {
+    context: C,
+    scenes: Vec<Box<dyn Scene<C>>>,
+}
+
+impl<C> SceneStack<C> {
+    pub fn new(context: C, first: Box<dyn Scene<C>>) -> Self {
+        let mut stack = SceneStack {
+            context,
+            scenes: vec![],
+        };
+        stack.push(first);
+        stack
+    }
+
+    pub fn update(&mut self, keystate: &KeyState) {
+        let transition = match self.scenes.last_mut() {
+            Some(top) => top.update(&mut self.context, keystate),
+            None => return,
+        };
+
+        match transition {
+            Transition::Stay => {}
+            Transition::Push(scene) => self.push(scene),
+            Transition::Pop => self.pop(),
+            Transition::Replace(scene) => {
+                self.pop_any();
+                self.push(scene);
+            }
+            Transition::Clear(scene) => {
+                while !self.scenes.is_empty() {
+                    self.pop_any();
+                }
+                self.push(scene);
+            }
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.visible()
+            .iter()
+            .for_each(|scene| scene.draw(&self.context, renderer));
+    }
+
+    fn push(&mut self, mut scene: Box<dyn Scene<C>>) {
+        scene.enter(&mut self.context);
+        self.scenes.push(scene);
+    }
+
+    // The bottom scene stays, or there'd be nothing left to run
+    fn pop(&mut self) {
+        if self.scenes.len() > 1 {
+            self.pop_any();
+        }
+    }
+
+    fn pop_any(&mut self) {
+        if let Some(mut scene) = self.scenes.pop() {
+            scene.exit(&mut self.context);
+        }
+    }
+
+    // Bottom to top, from the highest scene that isn't an overlay
+    fn visible(&self) -> &[Box<dyn Scene<C>>] {
+        let covered = self
+            .scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        &self.scenes[covered..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each scene writes down what happens to it
+    type Log = Vec<String>;
+
+    struct Logged {
+        name: &'static str,
+        overlay: bool,
+        next: fn() -> Transition<Log>,
+    }
+
+    impl Logged {
+        fn scene(name: &'static str, next: fn() -> Transition<Log>) -> Box<dyn Scene<Log>> {
+            Box::new(Logged {
+                name,
+                overlay: false,
+                next,
+            })
+        }
+
+        fn overlay(name: &'static str, next: fn() -> Transition<Log>) -> Box<dyn Scene<Log>> {
+            Box::new(Logged {
+                name,
+                overlay: true,
+                next,
+            })
+        }
+    }
+
+    impl Scene<Log> for Logged {
+        fn update(&mut self, log: &mut Log, _keystate: &KeyState) -> Transition<Log> {
+            log.push(format!("update {}", self.name));
+            (self.next)()
+        }
+
+        fn draw(&self, _log: &Log, _renderer: &Renderer) {}
+
+        fn enter(&mut self, log: &mut Log) {
+            log.push(format!("enter {}", self.name));
+        }
+
+        fn exit(&mut self, log: &mut Log) {
+            log.push(format!("exit {}", self.name));
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+    }
+
+    fn stay() -> Transition<Log> {
+        Transition::Stay
+    }
+
+    fn pop() -> Transition<Log> {
+        Transition::Pop
+    }
+
+    fn push_menu() -> Transition<Log> {
+        Transition::Push(Logged::overlay("menu", pop))
+    }
+
+    fn replace_with_end() -> Transition<Log> {
+        Transition::Replace(Logged::scene("end", stay))
+    }
+
+    fn start_over() -> Transition<Log> {
+        Transition::Clear(Logged::scene("title", stay))
+    }
+
+    fn visible_count(stack: &SceneStack<Log>) -> usize {
+        stack.visible().len()
+    }
+
+    #[test]
+    fn only_the_top_scene_updates() {
+        let mut stack = SceneStack::new(vec![], Logged::scene("game", push_menu));
+        let keystate = KeyState::new();
+
+        stack.update(&keystate);
+        stack.update(&keystate);
+        stack.update(&keystate);
+
+        assert_eq!(
+            stack.context,
+            vec![
+                "enter game",
+                "update game",
+                "enter menu",
+                "update menu",
+                "exit menu",
+                "update game",
+                "enter menu",
+            ]
+        );
+    }
+
+    #[test]
+    fn replacing_exits_the_old_scene_before_entering_the_new_one() {
+        let mut stack = SceneStack::new(vec![], Logged::scene("game", replace_with_end));
+
+        stack.update(&KeyState::new());
+
+        assert_eq!(
+            stack.context,
+            vec!["enter game", "update game", "exit game", "enter end"]
+        );
+        assert_eq!(stack.scenes.len(), 1);
+    }
+
+    #[test]
+    fn clearing_exits_every_scene_from_the_top_down() {
+        let mut stack = SceneStack::new(vec![], Logged::scene("game", stay));
+        stack.push(Logged::overlay("menu", start_over));
+
+        stack.update(&KeyState::new());
+
+        assert_eq!(
+            stack.context,
+            vec![
+                "enter game",
+                "enter menu",
+                "update menu",
+                "exit menu",
+                "exit game",
+                "enter title",
+            ]
+        );
+        assert_eq!(stack.scenes.len(), 1);
+    }
+
+    #[test]
+    fn the_last_scene_is_never_popped() {
+        let mut stack = SceneStack::new(vec![], Logged::scene("game", pop));
+
+        stack.update(&KeyState::new());
+
+        assert_eq!(stack.scenes.len(), 1);
+        assert_eq!(stack.context, vec!["enter game", "update game"]);
+    }
+
+    #[test]
+    fn overlays_show_what_is_under_them() {
+        let mut stack = SceneStack::new(vec![], Logged::scene("game", push_menu));
+        assert_eq!(visible_count(&stack), 1);
+
+        stack.update(&KeyState::new());
+        assert_eq!(visible_count(&stack), 2);
+
+        stack.push(Logged::scene("settings", stay));
+        assert_eq!(visible_count(&stack), 1);
+    }
+}
@@ -0,0 +1,402 @@
+use std::rc::Rc;
+
+use super::{KeyState, Point, Rect, Renderer, SpriteSheet};
+
+const ROW_HEIGHT: i32 = 40;
+const PADDING: i32 = 32;
+const FONT: &str = "20px 'Ken Future'";
+// The font's baseline sits this far under the middle of a row
+const TEXT_DROP: i32 = 7;
+const TEXT_COLOR: &str = "#FFFFFF";
+const FOCUS_COLOR: &str = "rgba(224, 160, 48, 0.8)";
+const TRACK_WIDTH: i32 = 120;
+const TRACK_HEIGHT: i32 = 10;
+const TRACK_COLOR: &str = "#3B2A1E";
+const FILL_COLOR: &str = "#9BD14B";
+const CHECK_SIZE: i32 = 20;
+const SLIDER_STEP: f32 = 0.1;
+
+// One row of a panel. Everything but a label can take the focus.
+pub enum Widget<T> {
+    Label(String),
+    Button { id: T, text: String },
+    // Between 0 and 1
+    Slider { id: T, text: String, value: f32 },
+    Toggle { id: T, text: String, on: bool },
+}
+
+impl<T> Widget<T> {
+    pub fn label(text: &str) -> Self {
+        Widget::Label(text.into())
+    }
+
+    pub fn button(id: T, text: &str) -> Self {
+        Widget::Button {
+            id,
+            text: text.into(),
+        }
+    }
+
+    pub fn slider(id: T, text: &str, value: f32) -> Self {
+        Widget::Slider {
+            id,
+            text: text.into(),
+            value: value.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn toggle(id: T, text: &str, on: bool) -> Self {
+        Widget::Toggle {
+            id,
+            text: text.into(),
+            on,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        !matches!(self, Widget::Label(_))
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Widget::Label(text)
+            | Widget::Button { text, .. }
+            | Widget::Slider { text, .. }
+            | Widget::Toggle { text, .. } => text,
+        }
+    }
+}
+
+// What the player did to a widget this tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UiEvent<T> {
+    Pressed(T),
+    Changed(T, f32),
+    Toggled(T, bool),
+}
+
+// A panel stretched from nine atlas tiles, the corners kept square at
+// `border` and the edges and middle stretched to fill the rest. Cells are
+// named left to right, top to bottom.
+pub struct NineSlice {
+    sheet: Rc<SpriteSheet>,
+    cells: [&'static str; 9],
+    border: i32,
+}
+
+impl NineSlice {
+    pub fn new(sheet: Rc<SpriteSheet>, cells: [&'static str; 9], border: i32) -> Self {
+        NineSlice {
+            sheet,
+            cells,
+            border,
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer, area: &Rect) {
+        let border = self.border.min(area.width() / 2).min(area.height() / 2);
+        let columns = [
+            (area.left(), border),
+            (area.left() + border, area.width() - border * 2),
+            (area.right() - border, border),
+        ];
+        let rows = [
+            (area.top(), border),
+            (area.top() + border, area.height() - border * 2),
+            (area.bottom() - border, border),
+        ];
+
+        // A missing tile leaves a hole rather than failing the whole panel
+        self.cells.iter().enumerate().for_each(|(index, name)| {
+            if let Some(cell) = self.sheet.cell(name) {
+                let (x, width) = columns[index % 3];
+                let (y, height) = rows[index / 3];
+                let source = Rect::new_from_x_y(
+                    cell.frame.x as i32,
+                    cell.frame.y as i32,
+                    cell.frame.w as i32,
+                    cell.frame.h as i32,
+                );
+                self.sheet
+                    .draw(renderer, &source, &Rect::new_from_x_y(x, y, width, height));
+            }
+        });
+    }
+}
+
+// A column of widgets on a panel, moved through with the arrow keys or the
+// pointer. Enter or a click presses whatever has the focus.
+pub struct Ui<T> {
+    widgets: Vec<Widget<T>>,
+    panel: Rect,
+    focused: Option<usize>,
+    pointer: Option<Point>,
+}
+
+impl<T: Copy> Ui<T> {
+    pub fn new(widgets: Vec<Widget<T>>, center: Point, width: i32) -> Self {
+        let height = PADDING * 2 + ROW_HEIGHT * widgets.len() as i32;
+        let panel = Rect::new_from_x_y(center.x - width / 2, center.y - height / 2, width, height);
+        let focused = widgets.iter().position(Widget::focusable);
+        Ui {
+            widgets,
+            panel,
+            focused,
+            pointer: None,
+        }
+    }
+
+    pub fn update(&mut self, keystate: &KeyState) -> Option<UiEvent<T>> {
+        if keystate.was_just_pressed("ArrowUp") {
+            self.move_focus(-1);
+        }
+        if keystate.was_just_pressed("ArrowDown") {
+            self.move_focus(1);
+        }
+
+        // Only a pointer that moves takes the focus, so one left resting on
+        // the panel doesn't fight the keyboard
+        let pointer = keystate.pointer();
+        if pointer != self.pointer {
+            self.pointer = pointer;
+            if let Some(index) = pointer.and_then(|pointer| self.widget_at(&pointer)) {
+                self.focused = Some(index);
+            }
+        }
+
+        if let Some(click) = keystate.was_just_clicked() {
+            return self.click(&click);
+        }
+
+        let index = self.focused?;
+        if keystate.was_just_pressed("ArrowLeft") {
+            return self.slide(index, -SLIDER_STEP);
+        }
+        if keystate.was_just_pressed("ArrowRight") {
+            return self.slide(index, SLIDER_STEP);
+        }
+        if keystate.was_just_pressed("Enter") {
+            return self.press(index);
+        }
+        None
+    }
+
+    // The focusable widget under `point`, if any
+    pub fn widget_at(&self, point: &Point) -> Option<usize> {
+        (0..self.widgets.len())
+            .find(|&index| self.widgets[index].focusable() && self.row(index).contains(point))
+    }
+
+    // Wraps around, past any labels
+    fn move_focus(&mut self, step: isize) {
+        let count = self.widgets.len() as isize;
+        let mut index = match self.focused {
+            Some(index) => index as isize,
+            None => return,
+        };
+        for _ in 0..count {
+            index = (index + step).rem_euclid(count);
+            if self.widgets[index as usize].focusable() {
+                self.focused = Some(index as usize);
+                return;
+            }
+        }
+    }
+
+    fn click(&mut self, point: &Point) -> Option<UiEvent<T>> {
+        let index = self.widget_at(point)?;
+        self.focused = Some(index);
+
+        // A slider jumps to wherever its track was clicked
+        let track = self.track(index);
+        if let Widget::Slider { id, value, .. } = &mut self.widgets[index] {
+            if !track.contains(point) {
+                return None;
+            }
+            *value = ((point.x - track.left()) as f32 / track.width() as f32).clamp(0.0, 1.0);
+            return Some(UiEvent::Changed(*id, *value));
+        }
+        self.press(index)
+    }
+
+    fn press(&mut self, index: usize) -> Option<UiEvent<T>> {
+        match &mut self.widgets[index] {
+            Widget::Button { id, .. } => Some(UiEvent::Pressed(*id)),
+            Widget::Toggle { id, on, .. } => {
+                *on = !*on;
+                Some(UiEvent::Toggled(*id, *on))
+            }
+            Widget::Label(_) | Widget::Slider { .. } => None,
+        }
+    }
+
+    fn slide(&mut self, index: usize, step: f32) -> Option<UiEvent<T>> {
+        match &mut self.widgets[index] {
+            Widget::Slider { id, value, .. } => {
+                *value = (*value + step).clamp(0.0, 1.0);
+                Some(UiEvent::Changed(*id, *value))
+            }
+            _ => None,
+        }
+    }
+
+    fn row(&self, index: usize) -> Rect {
+        Rect::new_from_x_y(
+            self.panel.x() + PADDING,
+            self.panel.y() + PADDING + ROW_HEIGHT * index as i32,
+            self.panel.width() - PADDING * 2,
+            ROW_HEIGHT,
+        )
+    }
+
+    // Sliders and toggles keep what they show at the right of their row
+    fn track(&self, index: usize) -> Rect {
+        let row = self.row(index);
+        Rect::new_from_x_y(
+            row.right() - TRACK_WIDTH - PADDING / 2,
+            row.center().y - TRACK_HEIGHT / 2,
+            TRACK_WIDTH,
+            TRACK_HEIGHT,
+        )
+    }
+
+    fn check(&self, index: usize) -> Rect {
+        let row = self.row(index);
+        Rect::new_from_x_y(
+            row.right() - CHECK_SIZE - PADDING / 2,
+            row.center().y - CHECK_SIZE / 2,
+            CHECK_SIZE,
+            CHECK_SIZE,
+        )
+    }
+
+    pub fn draw(&self, renderer: &Renderer, skin: &NineSlice) {
+        skin.draw(renderer, &self.panel);
+
+        self.widgets.iter().enumerate().for_each(|(index, widget)| {
+            let row = self.row(index);
+            if self.focused == Some(index) {
+                renderer.fill_rect(&row, FOCUS_COLOR);
+            }
+
+            let location = Point {
+                x: row.x() + PADDING / 2,
+                y: row.center().y + TEXT_DROP,
+            };
+            if let Err(err) = renderer.draw_styled_text(widget.text(), &location, FONT, TEXT_COLOR)
+            {
+                error!("Could not draw widget text {:#?}", err);
+            }
+
+            match widget {
+                Widget::Slider { value, .. } => {
+                    let track = self.track(index);
+                    renderer.fill_rect(&track, TRACK_COLOR);
+                    let filled = (track.width() as f32 * value).round() as i32;
+                    renderer.fill_rect(
+                        &Rect::new_from_x_y(track.x(), track.y(), filled, track.height()),
+                        FILL_COLOR,
+                    );
+                }
+                Widget::Toggle { on, .. } => {
+                    let check = self.check(index);
+                    renderer.fill_rect(&check, TRACK_COLOR);
+                    if *on {
+                        renderer.fill_rect(&check, FILL_COLOR);
+                    }
+                }
+                Widget::Label(_) | Widget::Button { .. } => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Id {
+        Play,
+        Volume,
+        Mute,
+    }
+
+    fn ui() -> Ui<Id> {
+        Ui::new(
+            vec![
+                Widget::label("Title"),
+                Widget::button(Id::Play, "Play"),
+                Widget::slider(Id::Volume, "Volume", 0.5),
+                Widget::toggle(Id::Mute, "Mute", false),
+            ],
+            Point { x: 300, y: 200 },
+            400,
+        )
+    }
+
+    fn pressing(code: &str) -> KeyState {
+        let mut keystate = KeyState::new();
+        keystate.set_pressed(code);
+        keystate
+    }
+
+    fn clicking(point: Point) -> KeyState {
+        let mut keystate = KeyState::new();
+        keystate.pointer = Some(point);
+        keystate.click = Some(point);
+        keystate
+    }
+
+    #[test]
+    fn focus_skips_labels_and_wraps_around() {
+        let mut ui = ui();
+        assert_eq!(ui.focused, Some(1));
+
+        ui.update(&pressing("ArrowUp"));
+        assert_eq!(ui.focused, Some(3));
+
+        ui.update(&pressing("ArrowDown"));
+        assert_eq!(ui.focused, Some(1));
+    }
+
+    #[test]
+    fn keys_press_slide_and_toggle() {
+        let mut ui = ui();
+        assert_eq!(
+            ui.update(&pressing("Enter")),
+            Some(UiEvent::Pressed(Id::Play))
+        );
+
+        ui.update(&pressing("ArrowDown"));
+        assert_eq!(
+            ui.update(&pressing("ArrowRight")),
+            Some(UiEvent::Changed(Id::Volume, 0.6))
+        );
+
+        ui.update(&pressing("ArrowDown"));
+        assert_eq!(
+            ui.update(&pressing("Enter")),
+            Some(UiEvent::Toggled(Id::Mute, true))
+        );
+    }
+
+    #[test]
+    fn clicks_hit_the_widget_under_them() {
+        let mut ui = ui();
+        let title = ui.row(0).center();
+        assert_eq!(ui.widget_at(&title), None);
+        assert_eq!(ui.update(&clicking(title)), None);
+
+        let track = ui.track(2);
+        let quarter = Point {
+            x: track.left() + track.width() / 4,
+            y: track.center().y,
+        };
+        assert_eq!(
+            ui.update(&clicking(quarter)),
+            Some(UiEvent::Changed(Id::Volume, 0.25))
+        );
+        assert_eq!(ui.focused, Some(2));
+    }
+}
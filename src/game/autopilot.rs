@@ -0,0 +1,80 @@
+use crate::engine::Rect;
+
+use super::{
+    hitboxes::{hitbox_for, WHOLE_FRAME},
+    Controls, Walk, HAZARD, SOLID,
+};
+
+// About a third of a second at running speed, time enough to leave the ground
+const LOOK_AHEAD: i32 = 60;
+
+// Plays the title screen's walk in the background. It only looks a little
+// way ahead, so it dies now and then, which is fine for a demo.
+pub fn controls(walk: &Walk) -> Controls {
+    let boy = walk.boy.bounding_box();
+    match walk.boy.state_name() {
+        // Holding jump goes as high as a jump goes
+        "Jump" => Controls {
+            jump: true,
+            ..Controls::default()
+        },
+        "Run" | "Slide" => react(walk, &boy),
+        _ => Controls::default(),
+    }
+}
+
+fn react(walk: &Walk, boy: &Rect) -> Controls {
+    let feet = boy.bottom();
+    let standing = hitbox_for("Run").unwrap_or(WHOLE_FRAME).height();
+    let sliding = hitbox_for("Slide").unwrap_or(WHOLE_FRAME).height();
+
+    // Measured against him standing up, so he stays down under an overhang
+    let ahead = Rect::new_from_x_y(
+        boy.left(),
+        feet - standing,
+        boy.width() + LOOK_AHEAD,
+        standing,
+    );
+    let blocked: Vec<Rect> = walk
+        .obstacles
+        .iter()
+        .filter(|obstacle| obstacle.layer().intersects(HAZARD.with(SOLID)))
+        .flat_map(|obstacle| obstacle.hitboxes())
+        .filter(|hitbox| hitbox.intersects(&ahead))
+        .collect();
+
+    if !blocked.is_empty() {
+        let slides_under = blocked
+            .iter()
+            .all(|hitbox| hitbox.bottom() <= feet - sliding);
+        return if slides_under {
+            Controls {
+                slide: true,
+                ..Controls::default()
+            }
+        } else {
+            jump()
+        };
+    }
+
+    let edge = boy.right() + LOOK_AHEAD;
+    let ground_ahead = walk
+        .obstacles
+        .iter()
+        .filter(|obstacle| obstacle.layer().intersects(SOLID))
+        .flat_map(|obstacle| obstacle.hitboxes())
+        .any(|hitbox| hitbox.left() <= edge && edge < hitbox.right() && hitbox.top() >= feet);
+    if ground_ahead {
+        Controls::default()
+    } else {
+        jump()
+    }
+}
+
+fn jump() -> Controls {
+    Controls {
+        jump: true,
+        fresh_jump: true,
+        slide: false,
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+
+use crate::engine::{Point, Rect, Renderer};
+
+use super::{Contact, Obstacle, RedHatBoy, HEIGHT, SOLID, WIDTH};
+
+// Enough to see what led up to a death without covering the screen
+const LOG_LENGTH: usize = 8;
+const LOG_POSITION: Point = Point { x: 20, y: 90 };
+const LINE_HEIGHT: i32 = 20;
+const BOY_HITBOX_COLOR: &str = "#00FF00";
+const OBSTACLE_HITBOX_COLOR: &str = "#FF0000";
+const SURFACE_COLOR: &str = "#00FFFF";
+const VELOCITY_COLOR: &str = "#FFFF00";
+const TIMELINE_COLOR: &str = "#FF00FF";
+// Velocities are a few pixels a tick, far too short to see drawn as they are
+const VELOCITY_SCALE: i32 = 8;
+
+// The boy touched something, and which state that left him in
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollisionEvent {
+    pub tick: u32,
+    pub obstacle: &'static str,
+    pub contact: Contact,
+    pub from: String,
+    pub to: String,
+}
+
+// Shows what the game sees rather than what it draws, and remembers the last
+// few collisions whether or not it's showing
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    tick: u32,
+    events: VecDeque<CollisionEvent>,
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn tick(&mut self) {
+        self.tick = self.tick.wrapping_add(1);
+    }
+
+    pub fn record(&mut self, obstacle: &'static str, contact: Contact, from: &str, to: &str) {
+        // Standing on the ground touches it every tick, which isn't news
+        if matches!(contact, Contact::Solid { .. }) && from == to {
+            return;
+        }
+
+        // Nor is touching the same thing tick after tick, like a stone while hurt
+        let tick = self.tick;
+        if let Some(last) = self.events.back_mut() {
+            if last.tick.wrapping_add(1) == tick
+                && last.obstacle == obstacle
+                && last.contact == contact
+                && last.from == from
+                && last.to == to
+            {
+                last.tick = tick;
+                return;
+            }
+        }
+
+        self.events.push_back(CollisionEvent {
+            tick,
+            obstacle,
+            contact,
+            from: from.into(),
+            to: to.into(),
+        });
+        if self.events.len() > LOG_LENGTH {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn draw(
+        &self,
+        renderer: &Renderer,
+        boy: &RedHatBoy,
+        obstacles: &[Box<dyn Obstacle>],
+        timeline: i32,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let boy_box = boy.bounding_box();
+        renderer.stroke_rect(&boy_box, BOY_HITBOX_COLOR);
+        obstacles.iter().for_each(|obstacle| {
+            let landable = obstacle.layer().intersects(SOLID);
+            obstacle.hitboxes().iter().for_each(|hitbox| {
+                renderer.stroke_rect(hitbox, OBSTACLE_HITBOX_COLOR);
+                if landable {
+                    draw_surface(renderer, hitbox);
+                }
+            });
+        });
+
+        let center = boy_box.center();
+        let velocity = (boy.velocity() * VELOCITY_SCALE).round();
+        renderer.stroke_path(
+            &[
+                center,
+                Point {
+                    x: center.x + velocity.x,
+                    y: center.y + velocity.y,
+                },
+            ],
+            VELOCITY_COLOR,
+        );
+
+        // The next segment usually starts off screen, so the marker waits at the edge
+        let marker = timeline.min(WIDTH - 1);
+        renderer.stroke_path(
+            &[
+                Point { x: marker, y: 0 },
+                Point {
+                    x: marker,
+                    y: HEIGHT,
+                },
+            ],
+            TIMELINE_COLOR,
+        );
+        self.draw_line(
+            renderer,
+            &format!("timeline {}", timeline),
+            Point {
+                x: marker - 120,
+                y: LINE_HEIGHT,
+            },
+        );
+
+        self.events.iter().enumerate().for_each(|(line, event)| {
+            self.draw_line(
+                renderer,
+                &format!(
+                    "{} {} {:?} {} -> {}",
+                    event.tick, event.obstacle, event.contact, event.from, event.to
+                ),
+                Point {
+                    x: LOG_POSITION.x,
+                    y: LOG_POSITION.y + line as i32 * LINE_HEIGHT,
+                },
+            )
+        });
+    }
+
+    fn draw_line(&self, renderer: &Renderer, text: &str, location: Point) {
+        if let Err(err) = renderer.draw_text(text, &location) {
+            error!("Could not draw debug text {:#?}", err);
+        }
+    }
+}
+
+// The top of a solid hitbox, where the boy can land
+fn draw_surface(renderer: &Renderer, hitbox: &Rect) {
+    renderer.stroke_path(
+        &[
+            Point {
+                x: hitbox.left(),
+                y: hitbox.top(),
+            },
+            Point {
+                x: hitbox.right(),
+                y: hitbox.top(),
+            },
+        ],
+        SURFACE_COLOR,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Normal;
+
+    const GROUND: Contact = Contact::Solid {
+        top: 500,
+        normal: Normal::Up,
+    };
+
+    #[test]
+    fn standing_on_the_ground_is_not_logged() {
+        let mut overlay = DebugOverlay::default();
+
+        overlay.record("platform", GROUND, "Run", "Run");
+        overlay.record("platform", GROUND, "Jump", "Run");
+
+        let logged: Vec<_> = overlay
+            .events
+            .iter()
+            .map(|event| event.from.as_str())
+            .collect();
+        assert_eq!(logged, vec!["Jump"]);
+    }
+
+    #[test]
+    fn a_touch_that_lasts_is_logged_once() {
+        let mut overlay = DebugOverlay::default();
+
+        (0..5).for_each(|_| {
+            overlay.record("stone", Contact::Lethal, "Hurt", "Hurt");
+            overlay.tick();
+        });
+        overlay.tick();
+        overlay.record("stone", Contact::Lethal, "Hurt", "Hurt");
+
+        let ticks: Vec<_> = overlay.events.iter().map(|event| event.tick).collect();
+        assert_eq!(ticks, vec![4, 6]);
+    }
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let mut overlay = DebugOverlay::default();
+
+        (0..LOG_LENGTH as u32 + 3).for_each(|_| {
+            overlay.record("coin", Contact::Collectible { score: 1 }, "Run", "Run");
+            overlay.tick();
+            overlay.tick();
+        });
+
+        let ticks: Vec<_> = overlay.events.iter().map(|event| event.tick).collect();
+        assert_eq!(ticks.len(), LOG_LENGTH);
+        assert_eq!(ticks[0], 6);
+    }
+}
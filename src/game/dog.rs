@@ -0,0 +1,501 @@
+use std::{collections::VecDeque, mem, rc::Rc};
+
+use web_sys::HtmlImageElement;
+
+use crate::engine::{physics, Audio, Point, Rect, Renderer, Sound};
+
+use super::{power_ups::SLOW_MOTION_FACTOR, Cell, Sheet};
+
+const IDLE_FRAME_NAME: &str = "Idle";
+const RUNNING_FRAME_NAME: &str = "Run";
+const JUMPING_FRAME_NAME: &str = "Jump";
+const BARKING_FRAME_NAME: &str = "Bark";
+const IDLE_TICKS_PER_IMAGE: u8 = 15;
+const IDLE_FRAMES: u8 = 2 * IDLE_TICKS_PER_IMAGE - 1;
+const RUNNING_TICKS_PER_IMAGE: u8 = 4;
+const RUNNING_FRAMES: u8 = 4 * RUNNING_TICKS_PER_IMAGE - 1;
+const BARKING_TICKS_PER_IMAGE: u8 = 6;
+const BARKING_FRAMES: u8 = 2 * BARKING_TICKS_PER_IMAGE - 1;
+// Where the dog's paws are in its frames
+const PAWS: i32 = 70;
+// Where the leash is tied, also in its frames
+const COLLAR: Point = Point { x: 70, y: 36 };
+
+// What the dog needs to know about the boy to walk in his footsteps, `y`
+// being where his feet are
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Footprint {
+    pub y: i32,
+    pub gait: Gait,
+    // Made in slow motion, when a tick is only part of one
+    pub slowed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gait {
+    Idle,
+    Running,
+    Jumping,
+    Down,
+}
+
+// The boy's last few footprints, each with when it was made. The dog is
+// `delay` ticks of running behind, which a slow motion tick only counts as
+// part of so he stays the same distance behind.
+pub struct Trail {
+    footprints: VecDeque<(Footprint, u32)>,
+    // In slow motion ticks, SLOW_MOTION_FACTOR to an ordinary one
+    clock: u32,
+    delay: usize,
+    startled: bool,
+}
+
+impl Trail {
+    pub fn new(delay: usize) -> Self {
+        Trail {
+            footprints: VecDeque::with_capacity(delay * SLOW_MOTION_FACTOR as usize + 1),
+            clock: 0,
+            delay,
+            startled: false,
+        }
+    }
+
+    // Records where the boy is now and returns where the dog should be
+    pub fn record(&mut self, footprint: Footprint) -> Footprint {
+        // The dog barks when the boy jumps or goes down, not when he lands or gets up
+        self.startled = matches!(footprint.gait, Gait::Jumping | Gait::Down)
+            && matches!(self.footprints.back(), Some((before, _)) if before.gait != footprint.gait);
+
+        self.clock += if footprint.slowed {
+            1
+        } else {
+            SLOW_MOTION_FACTOR as u32
+        };
+        self.footprints.push_back((footprint, self.clock));
+
+        // Keeps the last footprint made at least `delay` ago, to replay now
+        let replay_from = self
+            .clock
+            .saturating_sub(self.delay as u32 * SLOW_MOTION_FACTOR as u32);
+        while matches!(self.footprints.get(1), Some((_, made)) if *made <= replay_from) {
+            self.footprints.pop_front();
+        }
+        self.footprints
+            .front()
+            .map(|(footprint, _)| *footprint)
+            .unwrap_or(footprint)
+    }
+
+    pub fn is_startling(&self) -> bool {
+        self.startled
+    }
+}
+
+pub struct Dog {
+    state_machine: DogStateMachine,
+    sprite_sheet: Sheet,
+    image: HtmlImageElement,
+    trail: Trail,
+    x: i32,
+}
+
+impl Dog {
+    // `x` is where the dog is on screen, which has to be `delay` ticks of
+    // running behind the boy for the dog to land on what he landed on
+    pub fn new(
+        sprite_sheet: Sheet,
+        image: HtmlImageElement,
+        audio: Audio,
+        bark: Sound,
+        x: i32,
+        delay: usize,
+    ) -> Self {
+        Dog {
+            state_machine: DogStateMachine::Idle(DogState::new(Rc::new(audio), Rc::new(bark))),
+            sprite_sheet,
+            image,
+            trail: Trail::new(delay),
+            x,
+        }
+    }
+
+    pub fn follow(&mut self, footprint: Footprint) {
+        let replayed = self.trail.record(footprint);
+        if self.trail.is_startling() {
+            self.state_machine = self.state_machine.clone().transition(DogEvent::Bark);
+        }
+        self.state_machine = self
+            .state_machine
+            .clone()
+            .transition(DogEvent::Follow(replayed));
+    }
+
+    // Pulls the dog up or down until its collar is within `reach` of `anchor`.
+    // The dog keeps replaying the boy's path, so this only lasts for the tick.
+    pub fn restrain(&mut self, anchor: Point, reach: i32) {
+        let collar = self.collar();
+        let held = physics::limit_vertically(anchor, collar, reach);
+        self.state_machine.context_mut().paws += held.y - collar.y;
+    }
+
+    pub fn collar(&self) -> Point {
+        Point {
+            x: self.x + COLLAR.x,
+            y: self.state_machine.context().paws - PAWS + COLLAR.y,
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let sprite = match self.current_sprite() {
+            Some(sprite) => sprite,
+            None => return,
+        };
+
+        let source = Rect::new_from_x_y(
+            sprite.frame.x as i32,
+            sprite.frame.y as i32,
+            sprite.frame.w as i32,
+            sprite.frame.h as i32,
+        );
+        let destination = Rect::new_from_x_y(
+            self.x + sprite.sprite_source_size.x as i32,
+            self.state_machine.context().paws - PAWS + sprite.sprite_source_size.y as i32,
+            sprite.frame.w as i32,
+            sprite.frame.h as i32,
+        );
+
+        renderer.draw_image(&self.image, &source, &destination);
+    }
+
+    fn current_sprite(&self) -> Option<&Cell> {
+        let frame_name = format!(
+            "{} ({}).png",
+            self.state_machine.frame_name(),
+            self.state_machine.image_number()
+        );
+        self.sprite_sheet.frames.get(&frame_name)
+    }
+
+    pub fn reset(&mut self) {
+        let audio = self.state_machine.context().audio.as_ref().clone();
+        let bark = self.state_machine.context().bark.as_ref().clone();
+        *self = Dog::new(
+            mem::take(&mut self.sprite_sheet),
+            self.image.clone(),
+            audio,
+            bark,
+            self.x,
+            self.trail.delay,
+        );
+    }
+}
+
+enum DogEvent {
+    Follow(Footprint),
+    Bark,
+}
+
+#[derive(Clone)]
+enum DogStateMachine {
+    Idle(DogState<Idle>),
+    Running(DogState<Running>),
+    Jumping(DogState<Jumping>),
+    Barking(DogState<Barking>),
+}
+
+impl DogStateMachine {
+    fn transition(self, event: DogEvent) -> Self {
+        match (self, event) {
+            (DogStateMachine::Idle(state), DogEvent::Follow(footprint)) => state.follow(footprint),
+            (DogStateMachine::Idle(state), DogEvent::Bark) => state.bark(),
+
+            (DogStateMachine::Running(state), DogEvent::Follow(footprint)) => {
+                state.follow(footprint)
+            }
+            (DogStateMachine::Running(state), DogEvent::Bark) => state.bark(),
+
+            (DogStateMachine::Jumping(state), DogEvent::Follow(footprint)) => {
+                state.follow(footprint)
+            }
+            // No room for the barking animation in the air, but he can still be heard
+            (DogStateMachine::Jumping(state), DogEvent::Bark) => state.bark(),
+
+            (DogStateMachine::Barking(state), DogEvent::Follow(footprint)) => {
+                state.follow(footprint)
+            }
+            (DogStateMachine::Barking(state), DogEvent::Bark) => state.bark(),
+        }
+    }
+
+    fn frame_name(&self) -> &str {
+        match self {
+            DogStateMachine::Idle(_) => IDLE_FRAME_NAME,
+            DogStateMachine::Running(_) => RUNNING_FRAME_NAME,
+            DogStateMachine::Jumping(_) => JUMPING_FRAME_NAME,
+            DogStateMachine::Barking(_) => BARKING_FRAME_NAME,
+        }
+    }
+
+    fn image_number(&self) -> u8 {
+        match self {
+            DogStateMachine::Idle(state) => state.context.frame / IDLE_TICKS_PER_IMAGE + 1,
+            DogStateMachine::Running(state) => state.context.frame / RUNNING_TICKS_PER_IMAGE + 1,
+            // Legs tucked in on the way up, stretched out on the way down
+            DogStateMachine::Jumping(state) if state.context.velocity_y < 0 => 1,
+            DogStateMachine::Jumping(_) => 2,
+            DogStateMachine::Barking(state) => state.context.frame / BARKING_TICKS_PER_IMAGE + 1,
+        }
+    }
+
+    fn context(&self) -> &DogContext {
+        match self {
+            DogStateMachine::Idle(state) => &state.context,
+            DogStateMachine::Running(state) => &state.context,
+            DogStateMachine::Jumping(state) => &state.context,
+            DogStateMachine::Barking(state) => &state.context,
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut DogContext {
+        match self {
+            DogStateMachine::Idle(state) => &mut state.context,
+            DogStateMachine::Running(state) => &mut state.context,
+            DogStateMachine::Jumping(state) => &mut state.context,
+            DogStateMachine::Barking(state) => &mut state.context,
+        }
+    }
+}
+
+impl From<DogState<Idle>> for DogStateMachine {
+    fn from(state: DogState<Idle>) -> Self {
+        DogStateMachine::Idle(state)
+    }
+}
+
+impl From<DogState<Running>> for DogStateMachine {
+    fn from(state: DogState<Running>) -> Self {
+        DogStateMachine::Running(state)
+    }
+}
+
+impl From<DogState<Jumping>> for DogStateMachine {
+    fn from(state: DogState<Jumping>) -> Self {
+        DogStateMachine::Jumping(state)
+    }
+}
+
+impl From<DogState<Barking>> for DogStateMachine {
+    fn from(state: DogState<Barking>) -> Self {
+        DogStateMachine::Barking(state)
+    }
+}
+
+#[derive(Clone)]
+struct DogState<S> {
+    context: DogContext,
+    _state: S,
+}
+
+impl<S> DogState<S> {
+    fn into_state<T>(self, state: T) -> DogState<T> {
+        DogState {
+            context: self.context.reset_frame(),
+            _state: state,
+        }
+    }
+
+    // Whatever the dog is doing, it goes where the boy went
+    fn walk_to(self, footprint: Footprint) -> DogStateMachine {
+        match footprint.gait {
+            Gait::Idle | Gait::Down => self.into_state(Idle).step(footprint).into(),
+            Gait::Running => self.into_state(Running).step(footprint).into(),
+            Gait::Jumping => self.into_state(Jumping).step(footprint).into(),
+        }
+    }
+
+    fn step(mut self, footprint: Footprint) -> Self {
+        self.context = self.context.step(footprint);
+        self
+    }
+}
+
+impl DogState<Idle> {
+    fn new(audio: Rc<Audio>, bark: Rc<Sound>) -> Self {
+        DogState {
+            context: DogContext {
+                frame: 0,
+                paws: 0,
+                velocity_y: 0,
+                audio,
+                bark,
+            },
+            _state: Idle,
+        }
+    }
+
+    fn follow(mut self, footprint: Footprint) -> DogStateMachine {
+        if footprint.gait == Gait::Idle || footprint.gait == Gait::Down {
+            self.context = self.context.animate(IDLE_FRAMES).step(footprint);
+            self.into()
+        } else {
+            self.walk_to(footprint)
+        }
+    }
+
+    fn bark(self) -> DogStateMachine {
+        self.context.play_bark();
+        self.into_state(Barking).into()
+    }
+}
+
+impl DogState<Running> {
+    fn follow(mut self, footprint: Footprint) -> DogStateMachine {
+        if footprint.gait == Gait::Running {
+            self.context = self.context.animate(RUNNING_FRAMES).step(footprint);
+            self.into()
+        } else {
+            self.walk_to(footprint)
+        }
+    }
+
+    fn bark(self) -> DogStateMachine {
+        self.context.play_bark();
+        self.into_state(Barking).into()
+    }
+}
+
+impl DogState<Jumping> {
+    fn follow(self, footprint: Footprint) -> DogStateMachine {
+        if footprint.gait == Gait::Jumping {
+            self.step(footprint).into()
+        } else {
+            self.walk_to(footprint)
+        }
+    }
+
+    fn bark(self) -> DogStateMachine {
+        self.context.play_bark();
+        self.into()
+    }
+}
+
+impl DogState<Barking> {
+    // Barks on the spot, unless the boy jumped and the dog has to follow
+    fn follow(mut self, footprint: Footprint) -> DogStateMachine {
+        if footprint.gait == Gait::Jumping || self.context.frame >= BARKING_FRAMES {
+            self.walk_to(footprint)
+        } else {
+            self.context = self.context.animate(BARKING_FRAMES).step(footprint);
+            self.into()
+        }
+    }
+
+    fn bark(self) -> DogStateMachine {
+        self.context.play_bark();
+        self.into_state(Barking).into()
+    }
+}
+
+#[derive(Clone)]
+struct DogContext {
+    frame: u8,
+    paws: i32,
+    velocity_y: i32,
+    audio: Rc<Audio>,
+    bark: Rc<Sound>,
+}
+
+impl DogContext {
+    fn animate(mut self, frame_count: u8) -> Self {
+        if self.frame < frame_count {
+            self.frame += 1;
+        } else {
+            self.frame = 0;
+        }
+        self
+    }
+
+    fn reset_frame(mut self) -> Self {
+        self.frame = 0;
+        self
+    }
+
+    fn step(mut self, footprint: Footprint) -> Self {
+        self.velocity_y = footprint.y - self.paws;
+        self.paws = footprint.y;
+        self
+    }
+
+    fn play_bark(&self) {
+        if let Err(err) = self.audio.play_sound(&self.bark) {
+            log!("Error playing bark {:#?}", err);
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Idle;
+
+#[derive(Copy, Clone)]
+struct Running;
+
+#[derive(Copy, Clone)]
+struct Jumping;
+
+#[derive(Copy, Clone)]
+struct Barking;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_at(y: i32) -> Footprint {
+        Footprint {
+            y,
+            gait: Gait::Running,
+            slowed: false,
+        }
+    }
+
+    #[test]
+    fn the_dog_is_where_the_boy_was_a_delay_ago() {
+        let mut trail = Trail::new(3);
+
+        let replayed: Vec<i32> = (0..6).map(|y| trail.record(running_at(y)).y).collect();
+
+        assert_eq!(replayed, vec![0, 0, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn slow_motion_keeps_the_dog_the_same_distance_behind() {
+        let mut trail = Trail::new(3);
+        let slowed = |y| Footprint {
+            slowed: true,
+            ..running_at(y)
+        };
+
+        let replayed: Vec<i32> = (0..10).map(|y| trail.record(slowed(y)).y).collect();
+
+        assert_eq!(replayed, vec![0, 0, 0, 0, 0, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn a_jump_startles_the_dog_once() {
+        let mut trail = Trail::new(10);
+        trail.record(running_at(0));
+        let jump = Footprint {
+            y: -25,
+            gait: Gait::Jumping,
+            slowed: false,
+        };
+
+        trail.record(jump);
+        assert!(trail.is_startling());
+
+        trail.record(jump);
+        assert!(!trail.is_startling());
+
+        trail.record(running_at(0));
+        assert!(!trail.is_startling());
+    }
+}
@@ -0,0 +1,281 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::{
+    engine::{broad_phase::Layers, Point, Rect, Renderer, Sweep},
+    segments::Piece,
+};
+
+use super::{red_hat_boy_states::RUNNING_SPEED, Contact, Obstacle, HAZARD, WIDTH};
+
+const WALKER_WIDTH: i32 = 48;
+const WALKER_HEIGHT: i32 = 36;
+// On top of the world scrolling toward the boy
+const WALKER_SPEED: f32 = 2.0;
+const JUMPER_SIZE: i32 = 40;
+const HOP_HEIGHT: f32 = 160.0;
+const HOP_TICKS: f32 = 40.0;
+const FLYER_WIDTH: i32 = 56;
+pub const FLYER_HEIGHT: i32 = 30;
+const FLYER_SPEED: f32 = 1.0;
+pub const FLYER_BOB: i32 = 4;
+const BOB_TICKS: f32 = 30.0;
+const TICKS_PER_IMAGE: u8 = 6;
+const WALKER_COLOR: &str = "#5B2A86";
+const JUMPER_COLOR: &str = "#3FA34D";
+const FLYER_COLOR: &str = "#333344";
+const EYE_COLOR: &str = "#FFFFFF";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    // Runs along the ground toward the boy
+    Walker,
+    // Hops up and down on the spot
+    Jumper,
+    // Drifts toward the boy at head height
+    Flyer,
+}
+
+impl EnemyKind {
+    fn size(&self) -> (i32, i32) {
+        match self {
+            EnemyKind::Walker => (WALKER_WIDTH, WALKER_HEIGHT),
+            EnemyKind::Jumper => (JUMPER_SIZE, JUMPER_SIZE),
+            EnemyKind::Flyer => (FLYER_WIDTH, FLYER_HEIGHT),
+        }
+    }
+
+    // Where an enemy that started at `origin` is before it comes on screen
+    pub fn resting_box(&self, origin: Point) -> Rect {
+        let (width, height) = self.size();
+        Rect::new(origin, width, height)
+    }
+
+    // Where an enemy that started at `origin`, scrolled along with the world to
+    // wherever `origin` is on screen now, has got to. Enemies move by how far
+    // the world has scrolled since they came on screen rather than by ticks,
+    // which keeps them in step with the world through slow motion and lets
+    // the reachability check know where they will be.
+    pub fn bounding_box(&self, origin: Point) -> Rect {
+        let ticks = (WIDTH - origin.x).max(0) as f32 / RUNNING_SPEED as f32;
+        let position = match self {
+            EnemyKind::Walker => Point {
+                x: origin.x - (ticks * WALKER_SPEED) as i32,
+                y: origin.y,
+            },
+            EnemyKind::Jumper => Point {
+                x: origin.x,
+                y: origin.y - ((ticks * PI / HOP_TICKS).sin().abs() * HOP_HEIGHT).round() as i32,
+            },
+            EnemyKind::Flyer => Point {
+                x: origin.x - (ticks * FLYER_SPEED) as i32,
+                y: origin.y + ((ticks * TAU / BOB_TICKS).sin() * FLYER_BOB as f32).round() as i32,
+            },
+        };
+        self.resting_box(position)
+    }
+}
+
+pub struct Enemy {
+    kind: EnemyKind,
+    origin: Point,
+    bounding_box: Rect,
+    frame: u8,
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyKind, origin: Point) -> Self {
+        Enemy {
+            kind,
+            origin,
+            bounding_box: kind.bounding_box(origin),
+            frame: 0,
+        }
+    }
+
+    // Two images, swapped every few ticks
+    fn image(&self) -> i32 {
+        i32::from(self.frame / TICKS_PER_IMAGE % 2)
+    }
+
+    fn draw_walker(&self, renderer: &Renderer) {
+        let body = &self.bounding_box;
+        let stride = self.image() * 6;
+        [8 + stride, 20 - stride, 32 + stride]
+            .iter()
+            .for_each(|&leg| {
+                let leg = Rect::new_from_x_y(body.x() + leg, body.bottom() - 10, 6, 10);
+                renderer.fill_rect(&leg, WALKER_COLOR);
+            });
+        let shell = Rect::new_from_x_y(body.x(), body.y(), body.width(), body.height() - 10);
+        renderer.fill_rect(&shell, WALKER_COLOR);
+        renderer.fill_circle(
+            &Point {
+                x: body.x() + 8,
+                y: body.y() + 10,
+            },
+            4,
+            EYE_COLOR,
+        );
+    }
+
+    fn draw_jumper(&self, renderer: &Renderer) {
+        let body = &self.bounding_box;
+        renderer.fill_circle(&body.center(), JUMPER_SIZE / 2, JUMPER_COLOR);
+        renderer.fill_circle(
+            &Point {
+                x: body.x() + 12,
+                y: body.y() + 14,
+            },
+            5,
+            EYE_COLOR,
+        );
+    }
+
+    fn draw_flyer(&self, renderer: &Renderer) {
+        let body = &self.bounding_box;
+        let center = body.center();
+        // Wings up, then wings down
+        let wing_y = if self.image() == 0 {
+            body.y()
+        } else {
+            center.y
+        };
+        let wings = Rect::new_from_x_y(body.x(), wing_y, body.width(), body.height() / 2);
+        renderer.fill_rect(&wings, FLYER_COLOR);
+        renderer.fill_circle(&center, body.height() / 2, FLYER_COLOR);
+        renderer.fill_circle(
+            &Point {
+                x: center.x - 6,
+                y: center.y - 3,
+            },
+            3,
+            EYE_COLOR,
+        );
+    }
+}
+
+impl Obstacle for Enemy {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if sweep.impact(&self.bounding_box).is_some() {
+            Some(Contact::Lethal)
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        match self.kind {
+            EnemyKind::Walker => self.draw_walker(renderer),
+            EnemyKind::Jumper => self.draw_jumper(renderer),
+            EnemyKind::Flyer => self.draw_flyer(renderer),
+        }
+    }
+
+    // The enemy scrolls along with everything else until its own update moves it
+    fn move_horizontally(&mut self, x: i32) {
+        self.origin.x += x;
+        self.bounding_box.set_x(self.bounding_box.x() + x);
+    }
+
+    fn update(&mut self) {
+        self.bounding_box = self.kind.bounding_box(self.origin);
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    fn right(&self) -> i32 {
+        self.bounding_box.right()
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![self.bounding_box]
+    }
+
+    fn layer(&self) -> Layers {
+        HAZARD
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            EnemyKind::Walker => "walker",
+            EnemyKind::Jumper => "jumper",
+            EnemyKind::Flyer => "flyer",
+        }
+    }
+
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::Enemy(kind, origin) => {
+                *self = Enemy::new(*kind, *origin);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        hitboxes::hitbox_at,
+        red_hat_boy_states::{FLOOR, STARTING_POINT},
+    };
+
+    // `ticks` after coming on screen
+    fn on_screen_for(ticks: i32, y: i32) -> Point {
+        Point {
+            x: WIDTH - ticks * RUNNING_SPEED,
+            y,
+        }
+    }
+
+    #[test]
+    fn enemies_wait_until_they_are_on_screen() {
+        let origin = Point {
+            x: WIDTH + 100,
+            y: 0,
+        };
+
+        [EnemyKind::Walker, EnemyKind::Jumper, EnemyKind::Flyer]
+            .iter()
+            .for_each(|kind| {
+                let waiting = kind.bounding_box(origin);
+                assert_eq!((waiting.x(), waiting.y()), (origin.x, origin.y));
+            });
+    }
+
+    #[test]
+    fn walkers_close_in_faster_than_the_world_scrolls() {
+        let origin = on_screen_for(10, 0);
+
+        let walker = EnemyKind::Walker.bounding_box(origin);
+
+        assert_eq!(walker.x(), origin.x - 10 * WALKER_SPEED as i32);
+    }
+
+    #[test]
+    fn jumpers_land_where_they_took_off() {
+        let top = EnemyKind::Jumper.bounding_box(on_screen_for(HOP_TICKS as i32 / 2, 0));
+        let landed = EnemyKind::Jumper.bounding_box(on_screen_for(HOP_TICKS as i32, 0));
+
+        assert_eq!(top.y(), -HOP_HEIGHT as i32);
+        assert_eq!(landed.y(), 0);
+    }
+
+    #[test]
+    fn flyers_hit_running_boys_but_not_sliding_ones() {
+        let boy = Point {
+            x: STARTING_POINT,
+            y: FLOOR,
+        };
+        let running = hitbox_at("Run", boy).unwrap();
+        let sliding = hitbox_at("Slide", boy).unwrap();
+        let origin_y = sliding.top() - FLYER_HEIGHT - FLYER_BOB - 1;
+
+        (0..200).for_each(|ticks| {
+            let flyer = EnemyKind::Flyer.bounding_box(on_screen_for(ticks, origin_y));
+            assert!(flyer.bottom() < sliding.top(), "{:?}", flyer);
+            assert!(flyer.bottom() > running.top(), "{:?}", flyer);
+        });
+    }
+}
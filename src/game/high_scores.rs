@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+
+use crate::browser;
+
+const STORAGE_KEY: &str = "walk_the_dog_high_scores";
+const KEPT: usize = 5;
+
+// The best few scores, best first, kept in the browser between visits
+#[derive(Debug, Default, PartialEq)]
+pub struct HighScores {
+    scores: Vec<u32>,
+}
+
+impl HighScores {
+    // A browser without storage, or with scores it can't read, starts a fresh table
+    pub fn load() -> Self {
+        let text = browser::local_storage().and_then(|storage| {
+            storage
+                .get_item(STORAGE_KEY)
+                .map_err(|err| anyhow!("Could not read high scores {:#?}", err))
+        });
+        match text {
+            Ok(Some(text)) => HighScores::parse(&text),
+            Ok(None) => HighScores::default(),
+            Err(err) => {
+                error!("Could not load high scores {:#?}", err);
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        browser::local_storage()?
+            .set_item(STORAGE_KEY, &self.to_text())
+            .map_err(|err| anyhow!("Could not save high scores {:#?}", err))
+    }
+
+    // Whether the score made the table
+    pub fn insert(&mut self, score: u32) -> bool {
+        let position = self
+            .scores
+            .iter()
+            .position(|&kept| score > kept)
+            .unwrap_or(self.scores.len());
+        if position >= KEPT {
+            return false;
+        }
+
+        self.scores.insert(position, score);
+        self.scores.truncate(KEPT);
+        true
+    }
+
+    pub fn scores(&self) -> &[u32] {
+        &self.scores
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut scores: Vec<u32> = text
+            .split(',')
+            .filter_map(|score| score.trim().parse().ok())
+            .collect();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        scores.truncate(KEPT);
+        HighScores { scores }
+    }
+
+    fn to_text(&self) -> String {
+        self.scores
+            .iter()
+            .map(|score| score.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_survive_being_stored_as_text() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(120);
+        high_scores.insert(300);
+
+        assert_eq!(high_scores.to_text(), "300,120");
+        assert_eq!(HighScores::parse("300,120"), high_scores);
+    }
+
+    #[test]
+    fn unreadable_scores_are_dropped() {
+        let high_scores = HighScores::parse("40,lots,,90");
+
+        assert_eq!(high_scores.scores(), &[90, 40]);
+    }
+
+    #[test]
+    fn only_the_best_scores_are_kept() {
+        let mut high_scores = HighScores::parse("50,40,30,20,10");
+
+        assert!(!high_scores.insert(5));
+        assert!(high_scores.insert(35));
+        assert_eq!(high_scores.scores(), &[50, 40, 35, 30, 20]);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::engine::{Point, Rect};
+
+use super::red_hat_boy_states::PLAYER_HEIGHT;
+
+// Built in rather than fetched, the reachability check needs them before
+// anything has loaded
+const HITBOXES: &str = include_str!("hitboxes.txt");
+
+// The whole of an untrimmed frame down to his feet, bigger than any hitbox.
+// For the checks that have no sprite to fall back on when an animation has
+// no hitbox.
+pub const WHOLE_FRAME: Rect = Rect {
+    position: Point { x: 0, y: 0 },
+    width: 160,
+    height: PLAYER_HEIGHT,
+};
+
+thread_local! {
+    static SHAPES: HashMap<String, Rect> = parse(HITBOXES);
+}
+
+// Lines that aren't a name and four numbers are skipped, like the comments
+fn parse(text: &str) -> HashMap<String, Rect> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let numbers = fields
+                .map(|field| field.parse().ok())
+                .collect::<Option<Vec<i32>>>()?;
+            match numbers[..] {
+                [x, y, width, height] => {
+                    Some((name.to_string(), Rect::new_from_x_y(x, y, width, height)))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Relative to the boy's position
+pub fn hitbox_for(animation: &str) -> Option<Rect> {
+    SHAPES.with(|shapes| shapes.get(animation).copied())
+}
+
+// Places the hitbox of `animation` for a boy standing at `position`
+pub fn hitbox_at(animation: &str, position: Point) -> Option<Rect> {
+    hitbox_for(animation).map(|hitbox| place(hitbox, position))
+}
+
+pub fn place(hitbox: Rect, position: Point) -> Rect {
+    Rect::new_from_x_y(
+        position.x + hitbox.x(),
+        position.y + hitbox.y(),
+        hitbox.width(),
+        hitbox.height(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sliding_is_lower_than_running() {
+        let running = hitbox_for("Run").unwrap();
+        let sliding = hitbox_for("Slide").unwrap();
+
+        assert!(sliding.top() > running.top());
+        assert_eq!(sliding.bottom(), running.bottom());
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        let shapes = parse("# A comment\nRun 1 2 3 4\nJump 1 2 3\nSlide 1 2 three 4\n");
+
+        assert_eq!(shapes.len(), 1);
+        let run = shapes["Run"];
+        assert_eq!((run.x(), run.y(), run.width(), run.height()), (1, 2, 3, 4));
+    }
+
+    #[test]
+    fn every_hitbox_fits_in_a_whole_frame() {
+        parse(HITBOXES).values().for_each(|hitbox| {
+            assert!(hitbox.left() >= WHOLE_FRAME.left(), "{:?}", hitbox);
+            assert!(hitbox.right() <= WHOLE_FRAME.right(), "{:?}", hitbox);
+            assert!(hitbox.top() >= WHOLE_FRAME.top(), "{:?}", hitbox);
+            assert_eq!(hitbox.bottom(), WHOLE_FRAME.bottom(), "{:?}", hitbox);
+        });
+    }
+}
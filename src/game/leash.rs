@@ -0,0 +1,105 @@
+use crate::engine::{broad_phase::BroadPhase, physics::Rope, Point, Rect, Renderer, Sweep};
+
+use super::{Obstacle, HAZARD};
+
+const LINKS: usize = 12;
+// How far the dog can get from the boy's hand before it's pulled along
+pub const LEASH_LENGTH: i32 = 80;
+const LEASH_COLOR: &str = "#3B2A1A";
+const SNAGGED_COLOR: &str = "#FF0000";
+
+// The rope between the boy's hand and the dog's collar
+pub struct Leash {
+    rope: Rope,
+    snagged: bool,
+}
+
+impl Leash {
+    pub fn new(hand: Point, collar: Point) -> Self {
+        Leash {
+            rope: Rope::new(hand, collar, LINKS, LEASH_LENGTH),
+            snagged: false,
+        }
+    }
+
+    // Returns true when the leash has just caught on something, and not on
+    // every tick that it stays caught
+    pub fn update(
+        &mut self,
+        hand: Point,
+        collar: Point,
+        floor: i32,
+        obstacles: &[Box<dyn Obstacle>],
+        broad_phase: &BroadPhase,
+    ) -> bool {
+        self.rope.update(hand, collar, floor);
+
+        let snagged = self.catches_on(obstacles, broad_phase);
+        let caught = snagged && !self.snagged;
+        self.snagged = snagged;
+        caught
+    }
+
+    // Only things that would hurt the boy snag the leash, it can drag over the
+    // ground and platforms. The ends are left out, whatever the boy and the dog
+    // touch is up to them.
+    fn catches_on(&self, obstacles: &[Box<dyn Obstacle>], broad_phase: &BroadPhase) -> bool {
+        let points = self.rope.points();
+        points[1..points.len() - 1].iter().any(|point| {
+            let spot = Sweep::at(Rect::new(*point, 1, 1));
+            broad_phase
+                .query(spot.from.left(), spot.from.right(), HAZARD)
+                .into_iter()
+                .any(|index| obstacles[index].contact(&spot).is_some())
+        })
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let color = if self.snagged {
+            SNAGGED_COLOR
+        } else {
+            LEASH_COLOR
+        };
+        renderer.stroke_path(&self.rope.points(), color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{index_obstacles, Overhang};
+
+    const HAND: Point = Point { x: 0, y: 0 };
+    const COLLAR: Point = Point { x: 40, y: 0 };
+    const FLOOR: i32 = 200;
+
+    #[test]
+    fn the_leash_is_only_caught_once_on_the_same_obstacle() {
+        // Right under the hand, where the leash sags
+        let obstacles: Vec<Box<dyn Obstacle>> = vec![Box::new(Overhang::new(Rect::new_from_x_y(
+            -10, 10, 60, 100,
+        )))];
+        let mut leash = Leash::new(HAND, COLLAR);
+        let mut broad_phase = BroadPhase::default();
+        index_obstacles(&mut broad_phase, &obstacles);
+
+        let caught = (0..30)
+            .filter(|_| leash.update(HAND, COLLAR, FLOOR, &obstacles, &broad_phase))
+            .count();
+
+        assert_eq!(caught, 1);
+    }
+
+    #[test]
+    fn the_leash_is_not_caught_on_obstacles_out_of_reach() {
+        let obstacles: Vec<Box<dyn Obstacle>> =
+            vec![Box::new(Overhang::new(Rect::new_from_x_y(200, 0, 50, 50)))];
+        let mut leash = Leash::new(HAND, COLLAR);
+        let mut broad_phase = BroadPhase::default();
+        index_obstacles(&mut broad_phase, &obstacles);
+
+        let caught = (0..30).any(|_| leash.update(HAND, COLLAR, FLOOR, &obstacles, &broad_phase));
+
+        assert!(!caught);
+    }
+}
@@ -5,29 +5,98 @@ mod test_browser;
 
 use crate::browser;
 
+mod autopilot;
+mod debug;
+mod dog;
+mod enemies;
+mod high_scores;
+mod hitboxes;
+mod leash;
+mod obstacle_pool;
+mod power_ups;
+mod reachability;
 mod red_hat_boy_states;
+mod run_stats;
+mod scenes;
 
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::HashMap, mem, rc::Rc};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use futures::channel::mpsc::UnboundedReceiver;
 use rand::prelude::*;
 use serde::Deserialize;
 use web_sys::HtmlImageElement;
 
 use crate::{
-    engine::{self, Audio, Game, Image, KeyState, Point, Rect, Renderer, Sound, SpriteSheet},
-    segments::{self, stone_and_platform},
+    engine::{
+        self,
+        broad_phase::{BroadPhase, Layers},
+        mask::CollisionMask,
+        scene::SceneStack,
+        ui::NineSlice,
+        Audio, Fixed, Game, Image, KeyState, Music, Normal, Point, Rect, Renderer, Sound,
+        SpriteSheet, Sweep, Vector,
+    },
+    segments::{Piece, Segment, SEGMENTS},
 };
 
-const WIDTH: i16 = 1200;
-const HEIGHT: i16 = 600;
-const X_OFFSET: i16 = 18;
-const Y_OFFSET: i16 = 14;
-const WIDTH_OFFSET: i16 = 28;
-const OBSTACLE_BUFFER: i16 = 20;
-const TIMELINE_MINIMUM: i16 = 1000;
+use self::{
+    debug::DebugOverlay,
+    dog::{Dog, Footprint, Gait},
+    high_scores::HighScores,
+    leash::{Leash, LEASH_LENGTH},
+    power_ups::{PowerUps, SHIELD_GRACE},
+    reachability::Reachability,
+    run_stats::RunStats,
+    scenes::Title,
+};
+pub use self::{
+    enemies::{Enemy, EnemyKind, FLYER_BOB, FLYER_HEIGHT},
+    obstacle_pool::ObstaclePool,
+    power_ups::{PowerUp, PowerUpPickup, POWER_UPS},
+};
+
+const WIDTH: i32 = 1200;
+const HEIGHT: i32 = 600;
+// Only the top half of the 128 pixel ground tiles shows above the bottom of the canvas
+pub const GROUND_TOP: i32 = HEIGHT - 64;
+const TIMELINE_MINIMUM: i32 = 1000;
+const SEGMENT_CLEARED_SCORE: u32 = 50;
+const SCORE_POSITION: Point = Point { x: 20, y: 30 };
+const POWER_UPS_POSITION: Point = Point { x: 20, y: 55 };
+const LIVES_POSITION: Point = Point { x: 140, y: 30 };
+// Lives per run, unless the settings turn them off
+const STARTING_LIVES: u8 = 3;
+const HURT_GRACE: u16 = 90;
+const DEBUG_OVERLAY_KEY: &str = "KeyH";
+const OVERHANG_COLOR: &str = "#5B3A29";
+// How many ticks the dog runs behind the boy, and so how far behind him it is
+const DOG_DELAY_TICKS: usize = 12;
+// Lines the middle of the dog up with the middle of the boy's hitbox
+const DOG_X: i32 = STARTING_POINT + 49 - DOG_DELAY_TICKS as i32 * RUNNING_SPEED;
+const SNAG_PENALTY: u32 = 10;
+const PLATFORM_TRAVEL: f32 = 60.0;
+const PLATFORM_PERIOD: f32 = 120.0;
+const CRUMBLE_TICKS: u8 = 30;
+// Platforms move in whole pixels, so a crumbled one falls by its own rules
+const CRUMBLE_GRAVITY: i32 = 1;
+const CRUMBLE_FALL_SPEED: i32 = 20;
+const SPRINGBOARD_PAD: i32 = 12;
+const SPRINGBOARD_COLOR: &str = "#E0A030";
+const HAND: Point = Point { x: 8, y: 55 };
+// Ground tiles make the panels the menus sit on, grass along the top
+const PANEL_TILES: [&str; 9] = [
+    "1.png", "2.png", "7.png", "4.png", "5.png", "6.png", "12.png", "9.png", "16.png",
+];
+const PANEL_BORDER: i32 = 32;
+// Collision layers, what each obstacle is
+const SOLID: Layers = Layers(1);
+const HAZARD: Layers = Layers(1 << 1);
+const PICKUP: Layers = Layers(1 << 2);
+const TRIGGER: Layers = Layers(1 << 3);
+// and what checks against which. The dog collides with nothing at all.
+const BOY_COLLIDES_WITH: Layers = SOLID.with(HAZARD).with(PICKUP).with(TRIGGER);
+const MAGNET_PULLS: Layers = PICKUP;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SheetRect {
@@ -44,43 +113,56 @@ pub struct Cell {
     pub sprite_source_size: SheetRect,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Default, Deserialize, Clone)]
 pub struct Sheet {
     pub frames: HashMap<String, Cell>,
 }
 
 pub struct WalkTheDog {
-    pub machine: Option<WalkTheDogStateMachine>,
+    pub scenes: Option<SceneStack<Walk>>,
 }
 
 impl WalkTheDog {
     pub fn new() -> Self {
-        WalkTheDog { machine: None }
+        WalkTheDog { scenes: None }
     }
 }
 
 #[async_trait(?Send)]
 impl Game for WalkTheDog {
     async fn initialize(&self) -> Result<Box<dyn Game>> {
-        match self.machine {
+        match self.scenes {
             None => {
                 let json = browser::fetch_json("rhb.json").await?;
 
                 let audio = Audio::new()?;
                 let jump_sound = audio.load_sound("SFX_Jump_23.mp3").await?;
                 let background_sound = audio.load_sound("background_song.mp3").await?;
-                audio.play_looping_sound(&background_sound)?;
+                let music = audio.play_looping_sound(&background_sound)?;
+
+                let bark = audio.load_sound("bark.wav").await?;
+                let dog = Dog::new(
+                    browser::fetch_json("dog.json")
+                        .await?
+                        .into_serde::<Sheet>()?,
+                    engine::load_image("dog.svg").await?,
+                    audio.clone(),
+                    bark,
+                    DOG_X,
+                    DOG_DELAY_TICKS,
+                );
 
                 let boy = RedHatBoy::new(
                     json.into_serde::<Sheet>()?,
                     engine::load_image("rhb.png").await?,
                     audio,
                     jump_sound,
+                    Some(STARTING_LIVES),
                 );
 
                 let background = engine::load_image("BG.png").await?;
                 let first_background = Image::new(background.clone(), Point { x: 0, y: 0 });
-                let background_width = background.width() as i16;
+                let background_width = background.width() as i32;
                 let second_background = Image::new(
                     background,
                     Point {
@@ -90,31 +172,65 @@ impl Game for WalkTheDog {
                 );
 
                 let stone = engine::load_image("Stone.png").await?;
+                let stone_mask = CollisionMask::from_image(
+                    &stone,
+                    &Rect::new_from_x_y(0, 0, stone.width() as i32, stone.height() as i32),
+                )?;
+                let stone = Image::new(stone, Point { x: 0, y: 0 }).with_mask(Rc::new(stone_mask));
 
                 let tiles = browser::fetch_json("tiles.json").await?;
                 let tiles = tiles.into_serde::<Sheet>()?;
 
-                let sheet = SpriteSheet {
-                    sheet: tiles,
-                    image: engine::load_image("tiles.png").await?,
-                };
+                let sheet = SpriteSheet::new(tiles, engine::load_image("tiles.png").await?)?;
                 let sheet = Rc::new(sheet);
 
-                let starting_obstacles =
-                    segments::stone_and_platform(stone.clone(), sheet.clone(), 0);
+                let reachability =
+                    Reachability::new(stone.bounding_box().width(), stone.bounding_box().height());
+                if cfg!(debug_assertions) {
+                    for unsurvivable in reachability.unsurvivable() {
+                        error!(
+                            "Segment {:?} cannot be survived after {:?}, furthest reached {} with inputs {:?}",
+                            unsurvivable.next,
+                            unsurvivable.previous,
+                            unsurvivable.report.furthest,
+                            unsurvivable.report.inputs
+                        );
+                    }
+                }
+
+                let mut obstacle_pool = ObstaclePool::default();
+                let starting_obstacles = Segment::StoneAndPlatform.obstacles(
+                    &stone,
+                    sheet.clone(),
+                    0,
+                    &mut obstacle_pool,
+                );
                 let timeline = rightmost(&starting_obstacles);
 
+                let leash = Leash::new(boy.hand(), dog.collar());
                 let walk = Walk {
                     boy,
+                    dog,
+                    leash,
                     backgrounds: [first_background, second_background],
                     obstacles: starting_obstacles,
+                    obstacle_pool,
+                    broad_phase: BroadPhase::default(),
+                    skin: NineSlice::new(sheet.clone(), PANEL_TILES, PANEL_BORDER),
                     obstacle_sheet: sheet,
                     stone,
                     timeline,
+                    last_segment: Some(Segment::StoneAndPlatform),
+                    reachability,
+                    score: 0,
+                    debug: DebugOverlay::default(),
+                    music,
+                    high_scores: HighScores::load(),
+                    stats: RunStats::default(),
                 };
 
                 Ok(Box::new(WalkTheDog {
-                    machine: Some(WalkTheDogStateMachine::new(walk)),
+                    scenes: Some(SceneStack::new(walk, Box::new(Title::default()))),
                 }))
             }
             Some(_) => Err(anyhow!("Error: Game is already initialized!")),
@@ -122,134 +238,72 @@ impl Game for WalkTheDog {
     }
 
     fn update(&mut self, keystate: &KeyState) {
-        if let Some(machine) = self.machine.take() {
-            self.machine.replace(machine.update(keystate));
+        if let Some(scenes) = &mut self.scenes {
+            scenes.update(keystate);
         }
-        assert!(self.machine.is_some());
     }
 
     fn draw(&self, renderer: &Renderer) {
         let rect = Rect::new_from_x_y(0, 0, WIDTH, HEIGHT);
         renderer.clear(&rect);
 
-        if let Some(machine) = &self.machine {
-            machine.draw(renderer);
-        }
-    }
-}
-
-pub enum WalkTheDogStateMachine {
-    Ready(WalkTheDogState<Ready>),
-    Walking(WalkTheDogState<Walking>),
-    GameOver(WalkTheDogState<GameOver>),
-}
-
-impl WalkTheDogStateMachine {
-    fn new(walk: Walk) -> Self {
-        WalkTheDogStateMachine::Ready(WalkTheDogState::new(walk))
-    }
-
-    fn update(self, keystate: &KeyState) -> Self {
-        // log!("KeyState is {:#?}", keystate);
-        match self {
-            WalkTheDogStateMachine::Ready(state) => state.update(keystate).into(),
-            WalkTheDogStateMachine::Walking(state) => state.update(keystate).into(),
-            WalkTheDogStateMachine::GameOver(state) => state.update().into(),
-        }
-    }
-
-    fn draw(&self, renderer: &Renderer) {
-        match self {
-            WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
-            WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
-            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
+        if let Some(scenes) = &self.scenes {
+            scenes.draw(renderer);
         }
     }
 }
 
-pub struct WalkTheDogState<T> {
-    _state: T,
-    walk: Walk,
-}
-
-impl<T> WalkTheDogState<T> {
-    fn draw(&self, renderer: &Renderer) {
-        self.walk.draw(renderer);
-    }
-}
-
-pub struct Ready;
-
-pub struct Walking;
-
-pub struct GameOver {
-    new_game_event: UnboundedReceiver<()>,
-}
-
-impl GameOver {
-    fn new_game_pressed(&mut self) -> bool {
-        matches!(self.new_game_event.try_next(), Ok(Some(())))
-    }
+// What the boy is told to do for a tick
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Controls {
+    pub jump: bool,
+    // Only set for the tick the jump is first pressed
+    pub fresh_jump: bool,
+    pub slide: bool,
 }
 
-impl WalkTheDogState<Ready> {
-    fn new(walk: Walk) -> WalkTheDogState<Ready> {
-        WalkTheDogState {
-            _state: Ready,
-            walk,
+impl Controls {
+    pub fn from_keys(keystate: &KeyState) -> Self {
+        Controls {
+            jump: keystate.is_pressed("Space"),
+            fresh_jump: keystate.was_just_pressed("Space"),
+            slide: keystate.is_pressed("ArrowDown"),
         }
     }
-
-    fn update(mut self, keystate: &KeyState) -> ReadyEndState {
-        self.walk.boy.update();
-        if keystate.is_pressed("ArrowRight") {
-            ReadyEndState::Complete(self.start_running())
-        } else {
-            ReadyEndState::Continue(self)
-        }
-    }
-
-    fn start_running(mut self) -> WalkTheDogState<Walking> {
-        self.run_right();
-        WalkTheDogState {
-            _state: Walking,
-            walk: self.walk,
-        }
-    }
-
-    fn run_right(&mut self) {
-        self.walk.boy.run_right();
-    }
 }
 
-enum ReadyEndState {
-    Complete(WalkTheDogState<Walking>),
-    Continue(WalkTheDogState<Ready>),
+pub struct Walk {
+    pub boy: RedHatBoy,
+    pub dog: Dog,
+    pub leash: Leash,
+    pub backgrounds: [Image; 2],
+    pub obstacles: Vec<Box<dyn Obstacle>>,
+    pub obstacle_pool: ObstaclePool,
+    pub broad_phase: BroadPhase,
+    pub obstacle_sheet: Rc<SpriteSheet>,
+    pub stone: Image,
+    pub timeline: i32,
+    pub last_segment: Option<Segment>,
+    pub reachability: Reachability,
+    pub score: u32,
+    pub debug: DebugOverlay,
+    pub music: Music,
+    pub high_scores: HighScores,
+    pub skin: NineSlice,
+    pub stats: RunStats,
 }
 
-impl From<ReadyEndState> for WalkTheDogStateMachine {
-    fn from(state: ReadyEndState) -> Self {
-        match state {
-            ReadyEndState::Complete(walking) => walking.into(),
-            ReadyEndState::Continue(ready) => ready.into(),
-        }
+impl Walk {
+    pub fn velocity(&self) -> i32 {
+        -self.boy.walking_speed()
     }
-}
-
-impl WalkTheDogState<Walking> {
-    fn update(mut self, keystate: &KeyState) -> WalkingEndState {
-        if keystate.is_pressed("Space") {
-            self.walk.boy.jump()
-        }
 
-        if keystate.is_pressed("ArrowDown") {
-            self.walk.boy.slide()
-        }
-
-        self.walk.boy.update();
+    // One tick of the run, whoever is at the controls
+    pub fn step(&mut self, controls: Controls) {
+        self.boy.control(controls);
 
-        let walking_speed = self.walk.velocity();
-        let [first_background, second_background] = &mut self.walk.backgrounds;
+        let walking_speed = self.velocity();
+        let [first_background, second_background] = &mut self.backgrounds;
 
         first_background.move_horizontally(walking_speed);
         second_background.move_horizontally(walking_speed);
@@ -262,141 +316,136 @@ impl WalkTheDogState<Walking> {
             second_background.set_x(first_background.right())
         }
 
-        self.walk.obstacles.iter_mut().for_each(|obstacle| {
-            obstacle.move_horizontally(walking_speed);
-            obstacle.check_intersection(&mut self.walk.boy);
-        });
-
-        if self.walk.timeline < TIMELINE_MINIMUM {
-            self.walk.generate_next_segment();
-        } else {
-            self.walk.timeline += walking_speed;
-        }
-
-        if self.walk.is_dead() {
-            WalkingEndState::Complete(self.end_game())
-        } else {
-            WalkingEndState::Continue(self)
-        }
-    }
-
-    fn end_game(self) -> WalkTheDogState<GameOver> {
-        let receiver = browser::draw_ui("<button id='new_game'>New Game</button>")
-            .and_then(|_unit| browser::find_html_element_by_id("new_game"))
-            .map(engine::add_click_handler)
-            .unwrap();
-
-        WalkTheDogState {
-            _state: GameOver {
-                new_game_event: receiver,
+        let reachability = &mut self.reachability;
+        let last_segment = &mut self.last_segment;
+        let stone = &self.stone;
+        let sheet = &self.obstacle_sheet;
+        self.stats.obstacles_cleared += scroll_obstacles(
+            &mut self.obstacles,
+            &mut self.obstacle_pool,
+            &mut self.timeline,
+            walking_speed,
+            self.boy.bounding_box().left(),
+            |timeline, pool| {
+                // If nothing can safely follow, leave some open ground instead
+                *last_segment = next_segment(reachability, *last_segment);
+                last_segment.unwrap_or(Segment::OpenGround).obstacles(
+                    stone,
+                    sheet.clone(),
+                    timeline,
+                    pool,
+                )
             },
-            walk: self.walk,
-        }
-    }
-}
-
-enum WalkingEndState {
-    Complete(WalkTheDogState<GameOver>),
-    Continue(WalkTheDogState<Walking>),
-}
-
-impl WalkTheDogState<GameOver> {
-    fn update(mut self) -> GameOverEndState {
-        if self._state.new_game_pressed() {
-            GameOverEndState::Complete(self.new_game())
-        } else {
-            GameOverEndState::Continue(self)
+        );
+        self.stats.add_distance(self.boy.walking_speed());
+
+        if self.boy.power_ups().is_active(PowerUp::Magnet) {
+            let target = self.boy.bounding_box().center();
+            self.obstacles
+                .iter_mut()
+                .filter(|obstacle| obstacle.layer().intersects(MAGNET_PULLS))
+                .for_each(|obstacle| obstacle.attract(&target));
         }
-    }
-
-    fn new_game(self) -> WalkTheDogState<Ready> {
-        if let Err(err) = browser::hide_ui() {
-            error!("Error hiding the browser {:#?}", err);
+        index_obstacles(&mut self.broad_phase, &self.obstacles);
+        self.debug.tick();
+        self.touch_obstacles();
+
+        // There's nothing to land on below the screen, so no shield or life saves a fall
+        if self.boy.pos_y() > HEIGHT {
+            self.boy.knock_out();
+            self.stats.knocked_out_by("falling");
         }
+        self.walk_the_dog();
+    }
+
+    fn touch_obstacles(&mut self) {
+        // Landing only ever moves him up or down, so what's in reach along x stays the same
+        let reach = self.boy.sweep().bounds();
+        let nearby = self
+            .broad_phase
+            .query(reach.left(), reach.right(), BOY_COLLIDES_WITH);
+        let stride = self.boy.walking_speed();
+
+        for index in nearby {
+            let obstacle = &mut self.obstacles[index];
+            // Landing moves the boy, so his sweep is checked again for every obstacle.
+            // Obstacles are in order along the course, so he lands on the ground
+            // he's already on before reaching the edge of the next piece.
+            let contact = match obstacle.contact(&self.boy.sweep()) {
+                Some(contact) => contact,
+                None => continue,
+            };
+            let before = self.boy.state_name().to_string();
+
+            match contact {
+                Contact::Solid { top, normal } => {
+                    if self.boy.land_on_or_knock_out(top, normal) {
+                        obstacle.stand_on();
+                    }
+                }
+                Contact::Springboard { top, normal } => {
+                    if self.boy.land_on_or_knock_out(top, normal) {
+                        self.boy.spring();
+                    }
+                }
+                Contact::Lethal => self.boy.hit(),
+                Contact::Collectible { score } => {
+                    self.score += score;
+                    obstacle.consume();
+                }
+                Contact::PowerUp(power_up) => {
+                    self.boy.power_up(power_up);
+                    obstacle.consume();
+                }
+                Contact::Trigger(Trigger::SegmentCleared) => {
+                    self.score += SEGMENT_CLEARED_SCORE;
+                    obstacle.consume();
+                }
+            }
 
-        WalkTheDogState {
-            _state: Ready,
-            walk: Walk::reset(self.walk),
+            if before != "Dead" && self.boy.state_name() == "Dead" {
+                self.stats.knocked_out_by(obstacle.name());
+            }
+            self.debug
+                .record(obstacle.name(), contact, &before, self.boy.state_name());
         }
-    }
-}
-
-enum GameOverEndState {
-    Complete(WalkTheDogState<Ready>),
-    Continue(WalkTheDogState<GameOver>),
-}
-
-impl From<WalkTheDogState<Ready>> for WalkTheDogStateMachine {
-    fn from(state: WalkTheDogState<Ready>) -> Self {
-        WalkTheDogStateMachine::Ready(state)
-    }
-}
-
-impl From<WalkTheDogState<Walking>> for WalkTheDogStateMachine {
-    fn from(state: WalkTheDogState<Walking>) -> Self {
-        WalkTheDogStateMachine::Walking(state)
-    }
-}
-
-impl From<WalkTheDogState<GameOver>> for WalkTheDogStateMachine {
-    fn from(state: WalkTheDogState<GameOver>) -> Self {
-        WalkTheDogStateMachine::GameOver(state)
-    }
-}
 
-impl From<WalkingEndState> for WalkTheDogStateMachine {
-    fn from(end_state: WalkingEndState) -> Self {
-        match end_state {
-            WalkingEndState::Continue(state) => WalkTheDogStateMachine::Walking(state),
-            WalkingEndState::Complete(state) => WalkTheDogStateMachine::GameOver(state),
+        // He ran into something solid, so the world goes back to where it was
+        let blocked = stride - self.boy.walking_speed();
+        if blocked > 0 {
+            self.scroll_back(blocked);
         }
     }
-}
 
-impl From<GameOverEndState> for WalkTheDogStateMachine {
-    fn from(end_state: GameOverEndState) -> Self {
-        match end_state {
-            GameOverEndState::Continue(state) => WalkTheDogStateMachine::GameOver(state),
-            GameOverEndState::Complete(state) => WalkTheDogStateMachine::Ready(state),
+    fn scroll_back(&mut self, distance: i32) {
+        self.backgrounds
+            .iter_mut()
+            .for_each(|background| background.move_horizontally(distance));
+        self.obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.move_horizontally(distance));
+        self.timeline += distance;
+        self.stats.distance = self.stats.distance.saturating_sub(distance as u32);
+        index_obstacles(&mut self.broad_phase, &self.obstacles);
+    }
+
+    // The dog follows the boy's path as far as the leash lets it, and a leash
+    // that catches on something costs points
+    fn walk_the_dog(&mut self) {
+        self.dog.follow(self.boy.footprint());
+
+        let hand = self.boy.hand();
+        self.dog.restrain(hand, LEASH_LENGTH);
+        if self.leash.update(
+            hand,
+            self.dog.collar(),
+            GROUND_TOP,
+            &self.obstacles,
+            &self.broad_phase,
+        ) {
+            self.score = self.score.saturating_sub(SNAG_PENALTY);
         }
     }
-}
-
-pub struct Walk {
-    pub boy: RedHatBoy,
-    pub backgrounds: [Image; 2],
-    pub obstacles: Vec<Box<dyn Obstacle>>,
-    pub obstacle_sheet: Rc<SpriteSheet>,
-    pub stone: HtmlImageElement,
-    pub timeline: i16,
-}
-
-impl Walk {
-    pub fn velocity(&self) -> i16 {
-        -self.boy.walking_speed()
-    }
-
-    pub fn generate_next_segment(&mut self) {
-        let mut rng = thread_rng();
-        let next_segment = rng.gen_range(0..2);
-
-        let mut next_obstacles = match next_segment {
-            0 => segments::stone_and_platform(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            1 => segments::platform_and_stone(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            _ => vec![],
-        };
-
-        self.timeline = rightmost(&next_obstacles);
-        self.obstacles.append(&mut next_obstacles);
-    }
 
     fn draw(&self, renderer: &Renderer) {
         self.backgrounds.iter().for_each(|background| {
@@ -404,29 +453,58 @@ impl Walk {
         });
 
         self.boy.draw(renderer);
+        self.dog.draw(renderer);
+        self.leash.draw(renderer);
 
-        self.obstacles.iter().for_each(|obstacle| {
-            obstacle.draw(renderer);
-        });
+        // Whatever was collected this tick waits for the next one to be culled
+        self.obstacles
+            .iter()
+            .filter(|obstacle| !obstacle.consumed())
+            .for_each(|obstacle| {
+                obstacle.draw(renderer);
+            });
+
+        self.debug
+            .draw(renderer, &self.boy, &self.obstacles, self.timeline);
+
+        let screen = Rect::new_from_x_y(0, 0, WIDTH, HEIGHT);
+        self.boy
+            .power_ups()
+            .draw_effects(renderer, &self.boy.bounding_box(), &screen);
+
+        if let Err(err) = renderer.draw_text(&format!("Score {}", self.score), &SCORE_POSITION) {
+            error!("Could not draw score {:#?}", err);
+        }
+        if let Some(lives) = self.boy.lives() {
+            if let Err(err) = renderer.draw_text(&format!("Lives {}", lives), &LIVES_POSITION) {
+                error!("Could not draw lives {:#?}", err);
+            }
+        }
+        self.boy
+            .power_ups()
+            .draw_timers(renderer, &POWER_UPS_POSITION);
     }
 
     fn is_dead(&self) -> bool {
         self.boy.knocked_out()
     }
 
-    fn reset(walk: Self) -> Self {
-        let starting_obstacles =
-            stone_and_platform(walk.stone.clone(), walk.obstacle_sheet.clone(), 0);
-        let timeline = rightmost(&starting_obstacles);
+    fn reset(&mut self) {
+        self.obstacle_pool.release_all(&mut self.obstacles);
+        self.obstacles = Segment::StoneAndPlatform.obstacles(
+            &self.stone,
+            self.obstacle_sheet.clone(),
+            0,
+            &mut self.obstacle_pool,
+        );
+        self.timeline = rightmost(&self.obstacles);
+        self.last_segment = Some(Segment::StoneAndPlatform);
+        self.score = 0;
+        self.stats = RunStats::default();
 
-        Walk {
-            boy: RedHatBoy::reset(walk.boy),
-            backgrounds: walk.backgrounds,
-            obstacles: starting_obstacles,
-            obstacle_sheet: walk.obstacle_sheet,
-            stone: walk.stone,
-            timeline,
-        }
+        self.boy.reset();
+        self.dog.reset();
+        self.leash = Leash::new(self.boy.hand(), self.dog.collar());
     }
 }
 
@@ -436,6 +514,10 @@ pub struct RedHatBoy {
     state_machine: RedHatBoyStateMachine,
     sprite_sheet: Sheet,
     image: HtmlImageElement,
+    power_ups: PowerUps,
+    // None turns lives off, and the first hit is fatal
+    starting_lives: Option<u8>,
+    lives: Option<u8>,
 }
 
 impl RedHatBoy {
@@ -444,54 +526,73 @@ impl RedHatBoy {
         image: HtmlImageElement,
         audio: Audio,
         jump_sound: Sound,
+        starting_lives: Option<u8>,
     ) -> Self {
         RedHatBoy {
-            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(audio, jump_sound)),
+            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(
+                Rc::new(audio),
+                Rc::new(jump_sound),
+            )),
             sprite_sheet,
             image,
+            power_ups: PowerUps::default(),
+            starting_lives,
+            lives: starting_lives,
         }
     }
 
     pub fn draw(&self, renderer: &Renderer) {
+        if self.power_ups.is_blinking() {
+            return;
+        }
+
         let sprite = self.current_sprite().expect("Cell not found!");
 
         let position = Point {
-            x: sprite.frame.x as i16,
-            y: sprite.frame.y as i16,
+            x: sprite.frame.x as i32,
+            y: sprite.frame.y as i32,
         };
-        let width = sprite.frame.w as i16;
-        let height = sprite.frame.h as i16;
+        let width = sprite.frame.w as i32;
+        let height = sprite.frame.h as i32;
         let source = Rect::new(position, width, height);
 
         let destination = self.destination_box();
 
         renderer.draw_image(&self.image, &source, &destination);
-        // renderer.draw_rect(&self.bounding_box());
     }
 
     pub fn bounding_box(&self) -> Rect {
-        let mut bounding_box = self.destination_box();
-        bounding_box.position.x += X_OFFSET;
-        bounding_box.width -= WIDTH_OFFSET;
-        bounding_box.position.y += Y_OFFSET;
-        bounding_box.height -= Y_OFFSET;
-        bounding_box
+        hitboxes::hitbox_at(
+            self.state_machine.frame_name(),
+            self.state_machine.context().position.round(),
+        )
+        .unwrap_or_else(|| self.destination_box())
+    }
+
+    // How his bounding box moved through the world in the last update, which
+    // scrolled by his stride rather than him moving
+    pub fn sweep(&self) -> Sweep {
+        let context = self.state_machine.context();
+        let motion = Point {
+            x: context.stride,
+            y: context.position.y.round() - context.last_position.y.round(),
+        };
+        Sweep::ending_at(self.bounding_box(), motion)
     }
 
     pub fn destination_box(&self) -> Rect {
         let sprite = self.current_sprite().expect("Cell not found!");
 
-        let x = self.state_machine.context().position.x;
-        let y = self.state_machine.context().position.y;
-        let sprite_x = sprite.sprite_source_size.x as i16;
-        let sprite_y = sprite.sprite_source_size.y as i16;
+        let Point { x, y } = self.state_machine.context().position.round();
+        let sprite_x = sprite.sprite_source_size.x as i32;
+        let sprite_y = sprite.sprite_source_size.y as i32;
 
         let position = Point {
             x: x + sprite_x,
             y: y + sprite_y,
         };
-        let width = sprite.frame.w as i16;
-        let height = sprite.frame.h as i16;
+        let width = sprite.frame.w as i32;
+        let height = sprite.frame.h as i32;
         Rect::new(position, width, height)
     }
 
@@ -501,11 +602,39 @@ impl RedHatBoy {
 
     pub fn frame_name(&self) -> String {
         let name = self.state_machine.frame_name();
-        let number = (self.state_machine.context().frame / 3) + 1;
+        let number =
+            (self.state_machine.context().frame / self.state_machine.ticks_per_image()) + 1;
         format!("{} ({}).png", name, number)
     }
 
+    // One tick of input and movement
+    pub fn control(&mut self, controls: Controls) {
+        self.tick_power_ups();
+
+        // A fresh press while already in the air, checked first so the press
+        // that starts a jump can't also spend the double jump
+        if controls.fresh_jump {
+            self.double_jump()
+        }
+
+        if controls.jump {
+            self.jump()
+        } else {
+            self.release_jump()
+        }
+
+        if controls.slide {
+            self.slide()
+        } else {
+            self.release_slide()
+        }
+
+        self.update();
+    }
+
     pub fn update(&mut self) {
+        self.state_machine.context_mut().slow_motion =
+            self.power_ups.is_active(PowerUp::SlowMotion);
         self.state_machine = self.state_machine.clone().transition(Event::Update)
     }
 
@@ -521,54 +650,186 @@ impl RedHatBoy {
         self.state_machine = self.state_machine.clone().transition(Event::Jump);
     }
 
+    pub fn release_slide(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(Event::ReleaseSlide);
+    }
+
+    pub fn release_jump(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(Event::ReleaseJump);
+    }
+
+    pub fn double_jump(&mut self) {
+        if self.power_ups.is_active(PowerUp::DoubleJump) {
+            self.state_machine = self.state_machine.clone().transition(Event::DoubleJump);
+        }
+    }
+
+    pub fn power_up(&mut self, power_up: PowerUp) {
+        self.power_ups.activate(power_up);
+    }
+
+    pub fn power_ups(&self) -> &PowerUps {
+        &self.power_ups
+    }
+
+    fn tick_power_ups(&mut self) {
+        self.power_ups.tick();
+    }
+
+    // Anything that would knock the boy out goes through here, so a shield
+    // or a spare life can take the hit instead
+    pub fn hit(&mut self) {
+        if self.power_ups.is_invulnerable() {
+            return;
+        }
+
+        if self.power_ups.is_active(PowerUp::Shield) {
+            self.power_ups.end(PowerUp::Shield);
+            self.power_ups.make_invulnerable(SHIELD_GRACE);
+            return;
+        }
+
+        match self.lives {
+            Some(lives) if lives > 1 => {
+                // Only some states can be hurt, a life is only lost to one that is
+                let hurt = self.state_machine.clone().transition(Event::Hurt);
+                if matches!(hurt, RedHatBoyStateMachine::Hurt(_)) {
+                    self.lives = Some(lives - 1);
+                    self.power_ups.make_invulnerable(HURT_GRACE);
+                    self.state_machine = hurt;
+                }
+            }
+            _ => self.knock_out(),
+        }
+    }
+
+    pub fn lives(&self) -> Option<u8> {
+        self.lives
+    }
+
+    pub fn starting_lives(&self) -> Option<u8> {
+        self.starting_lives
+    }
+
+    // Takes effect from the next run, a run in progress keeps its lives
+    pub fn set_starting_lives(&mut self, lives: Option<u8>) {
+        self.starting_lives = lives;
+    }
+
     pub fn knock_out(&mut self) {
         // error!("Knock out!");
         // panic!();
         self.state_machine = self.state_machine.clone().transition(Event::KnockOut);
     }
 
+    // Where he holds the leash, near the back of his hitbox
+    pub fn hand(&self) -> Point {
+        let hitbox = self.bounding_box();
+        Point {
+            x: hitbox.left() + HAND.x,
+            y: hitbox.top() + HAND.y,
+        }
+    }
+
+    pub fn footprint(&self) -> Footprint {
+        let gait = match self.state_machine {
+            RedHatBoyStateMachine::Idle(_) => Gait::Idle,
+            RedHatBoyStateMachine::Jumping(_) => Gait::Jumping,
+            RedHatBoyStateMachine::Falling(_) | RedHatBoyStateMachine::Dead(_) => Gait::Down,
+            _ => Gait::Running,
+        };
+
+        Footprint {
+            y: self.pos_y() + PLAYER_HEIGHT,
+            gait,
+            slowed: self.state_machine.context().slow_motion,
+        }
+    }
+
     pub fn knocked_out(&self) -> bool {
         self.state_machine.is_dead()
     }
 
-    pub fn land_on(&mut self, position: i16) {
+    pub fn land_on(&mut self, position: i32) {
         self.state_machine = self.state_machine.clone().transition(Event::Land(position));
     }
 
-    pub fn _velocity_x(&self) -> i16 {
-        self.state_machine.context().velocity.x
+    // Returns whether he landed, which is only when he came down onto the top
+    pub fn land_on_or_knock_out(&mut self, top: i32, normal: Normal) -> bool {
+        let landed = normal == Normal::Up;
+        if landed {
+            self.land_on(top);
+        } else {
+            self.bump(normal);
+            self.hit();
+        }
+        landed
+    }
+
+    // Something solid stops him whether or not it can hurt him. Running into
+    // its side takes away his stride, for the walk to scroll back.
+    fn bump(&mut self, normal: Normal) {
+        let context = self.state_machine.context_mut();
+        match normal {
+            Normal::Left => context.stride = 0,
+            Normal::Down => {
+                context.position.y = context.last_position.y;
+                context.velocity.y = context.velocity.y.max(Fixed::ZERO);
+            }
+            Normal::Up | Normal::Right => {}
+        }
+    }
+
+    pub fn spring(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(Event::Spring);
+    }
+
+    pub fn velocity(&self) -> Vector {
+        self.state_machine.context().velocity
     }
 
-    pub fn velocity_y(&self) -> i16 {
-        self.state_machine.context().velocity.y
+    // Which state he's in, named after its animation
+    pub fn state_name(&self) -> &str {
+        self.state_machine.frame_name()
     }
 
-    pub fn _pos_x(&self) -> i16 {
-        self.state_machine.context().position.x
+    pub fn _pos_x(&self) -> i32 {
+        self.state_machine.context().position.x.round()
     }
 
-    pub fn pos_y(&self) -> i16 {
-        self.state_machine.context().position.y
+    pub fn pos_y(&self) -> i32 {
+        self.state_machine.context().position.y.round()
     }
 
-    pub fn walking_speed(&self) -> i16 {
-        self.state_machine.context().velocity.x
+    pub fn walking_speed(&self) -> i32 {
+        self.state_machine.context().stride
     }
 
-    pub fn reset(boy: Self) -> Self {
-        let audio = boy.state_machine.context().audio.clone();
-        let jump_sound = boy.state_machine.context().jump_sound.clone();
-        RedHatBoy::new(boy.sprite_sheet, boy.image, audio, jump_sound)
+    pub fn reset(&mut self) {
+        let audio = self.state_machine.context().audio.as_ref().clone();
+        let jump_sound = self.state_machine.context().jump_sound.as_ref().clone();
+        *self = RedHatBoy::new(
+            mem::take(&mut self.sprite_sheet),
+            self.image.clone(),
+            audio,
+            jump_sound,
+            self.starting_lives,
+        );
     }
 }
 
 pub enum Event {
     Run,
     Slide,
+    ReleaseSlide,
     Update,
     Jump,
+    ReleaseJump,
+    DoubleJump,
+    Hurt,
     KnockOut,
-    Land(i16),
+    Land(i32),
+    Spring,
 }
 
 #[derive(Clone)]
@@ -577,6 +838,7 @@ enum RedHatBoyStateMachine {
     Running(RedHatBoyState<Running>),
     Sliding(RedHatBoyState<Sliding>),
     Jumping(RedHatBoyState<Jumping>),
+    Hurt(RedHatBoyState<Hurt>),
     Falling(RedHatBoyState<Falling>),
     Dead(RedHatBoyState<Dead>),
 }
@@ -591,23 +853,47 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::Running(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Running(state), Event::Jump) => state.jump().into(),
             (RedHatBoyStateMachine::Running(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Running(state), Event::Hurt) => state.hurt().into(),
             (RedHatBoyStateMachine::Running(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
+            (RedHatBoyStateMachine::Running(state), Event::Spring) => state.spring().into(),
 
             (RedHatBoyStateMachine::Sliding(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::Slide) => state.hold().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::ReleaseSlide) => state.release().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::Hurt) => state.hurt().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
+            (RedHatBoyStateMachine::Sliding(state), Event::Spring) => state.spring().into(),
 
             (RedHatBoyStateMachine::Jumping(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::Jump) => state.buffer_jump().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::Slide) => state.fast_fall().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::ReleaseJump) => {
+                state.release_jump().into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::DoubleJump) => {
+                state.double_jump().into()
+            }
             (RedHatBoyStateMachine::Jumping(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::Hurt) => state.hurt().into(),
             (RedHatBoyStateMachine::Jumping(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
 
+            (RedHatBoyStateMachine::Hurt(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Hurt(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Hurt(state), Event::Land(position)) => {
+                state.land_on(position).into()
+            }
+
             (RedHatBoyStateMachine::Falling(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Falling(state), Event::Land(position)) => {
+                state.land_on(position).into()
+            }
 
             _ => self,
         }
@@ -619,22 +905,43 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => state.frame_name(),
             RedHatBoyStateMachine::Sliding(state) => state.frame_name(),
             RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
+            RedHatBoyStateMachine::Hurt(state) => state.frame_name(),
             RedHatBoyStateMachine::Falling(state) => state.frame_name(),
             RedHatBoyStateMachine::Dead(state) => state.frame_name(),
         }
     }
 
+    fn ticks_per_image(&self) -> u8 {
+        match self {
+            RedHatBoyStateMachine::Sliding(_) => SLIDING_TICKS_PER_IMAGE,
+            _ => TICKS_PER_IMAGE,
+        }
+    }
+
     fn context(&self) -> &RedHatBoyContext {
         match self {
             RedHatBoyStateMachine::Idle(state) => state.context(),
             RedHatBoyStateMachine::Running(state) => state.context(),
             RedHatBoyStateMachine::Sliding(state) => state.context(),
             RedHatBoyStateMachine::Jumping(state) => state.context(),
+            RedHatBoyStateMachine::Hurt(state) => state.context(),
             RedHatBoyStateMachine::Falling(state) => state.context(),
             RedHatBoyStateMachine::Dead(state) => state.context(),
         }
     }
 
+    fn context_mut(&mut self) -> &mut RedHatBoyContext {
+        match self {
+            RedHatBoyStateMachine::Idle(state) => &mut state.context,
+            RedHatBoyStateMachine::Running(state) => &mut state.context,
+            RedHatBoyStateMachine::Sliding(state) => &mut state.context,
+            RedHatBoyStateMachine::Jumping(state) => &mut state.context,
+            RedHatBoyStateMachine::Hurt(state) => &mut state.context,
+            RedHatBoyStateMachine::Falling(state) => &mut state.context,
+            RedHatBoyStateMachine::Dead(state) => &mut state.context,
+        }
+    }
+
     fn is_dead(&self) -> bool {
         matches!(self, RedHatBoyStateMachine::Dead(_))
     }
@@ -664,6 +971,21 @@ impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Hurt>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Hurt>) -> Self {
+        RedHatBoyStateMachine::Hurt(state)
+    }
+}
+
+impl From<HurtEndState> for RedHatBoyStateMachine {
+    fn from(end_state: HurtEndState) -> Self {
+        match end_state {
+            HurtEndState::Complete(running_state) => running_state.into(),
+            HurtEndState::Hurt(hurt_state) => hurt_state.into(),
+        }
+    }
+}
+
 impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
     fn from(state: RedHatBoyState<Falling>) -> Self {
         RedHatBoyStateMachine::Falling(state)
@@ -685,11 +1007,20 @@ impl From<SlidingEndState> for RedHatBoyStateMachine {
     }
 }
 
-impl From<JumpingEndState> for RedHatBoyStateMachine {
-    fn from(end_state: JumpingEndState) -> Self {
+impl From<JumpEndState> for RedHatBoyStateMachine {
+    fn from(end_state: JumpEndState) -> Self {
+        match end_state {
+            JumpEndState::Running(running_state) => running_state.into(),
+            JumpEndState::Jumping(jumping_state) => jumping_state.into(),
+        }
+    }
+}
+
+impl From<SlideEndState> for RedHatBoyStateMachine {
+    fn from(end_state: SlideEndState) -> Self {
         match end_state {
-            JumpingEndState::Landing(running_state) => running_state.into(),
-            JumpingEndState::Jumping(jumping_state) => jumping_state.into(),
+            SlideEndState::Running(running_state) => running_state.into(),
+            SlideEndState::Sliding(sliding_state) => sliding_state.into(),
         }
     }
 }
@@ -703,7 +1034,58 @@ impl From<FallingEndState> for RedHatBoyStateMachine {
     }
 }
 
-pub fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
+pub fn index_obstacles(broad_phase: &mut BroadPhase, obstacles: &[Box<dyn Obstacle>]) {
+    broad_phase.rebuild(
+        obstacles
+            .iter()
+            .map(|obstacle| (obstacle.bounds(), obstacle.layer())),
+    );
+}
+
+// Scrolls the obstacles along with the world, moves anything done with into
+// the pool and asks `spawn` for the next segment once the course runs short.
+// Returns how many hazards went past `behind`.
+pub fn scroll_obstacles(
+    obstacles: &mut Vec<Box<dyn Obstacle>>,
+    pool: &mut ObstaclePool,
+    timeline: &mut i32,
+    distance: i32,
+    behind: i32,
+    spawn: impl FnOnce(i32, &mut ObstaclePool) -> Vec<Box<dyn Obstacle>>,
+) -> u32 {
+    let mut cleared = 0;
+    obstacles.iter_mut().for_each(|obstacle| {
+        let before = obstacle.right();
+        obstacle.move_horizontally(distance);
+        obstacle.update();
+        let passed = before >= behind && obstacle.right() < behind;
+        if passed && obstacle.layer().intersects(HAZARD) {
+            cleared += 1;
+        }
+    });
+    pool.cull(obstacles);
+
+    // The timeline scrolls with the obstacles so the next segment's ground
+    // starts exactly where the last one ends
+    *timeline += distance;
+    if *timeline < TIMELINE_MINIMUM {
+        let mut next = spawn(*timeline, pool);
+        *timeline = rightmost(&next);
+        obstacles.append(&mut next);
+    }
+    cleared
+}
+
+fn next_segment(reachability: &mut Reachability, last_segment: Option<Segment>) -> Option<Segment> {
+    let candidates: Vec<Segment> = SEGMENTS
+        .iter()
+        .copied()
+        .filter(|&segment| reachability.is_survivable(last_segment, segment))
+        .collect();
+    candidates.choose(&mut thread_rng()).copied()
+}
+
+pub fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i32 {
     obstacle_list
         .iter()
         .map(|obstacle| obstacle.right())
@@ -711,6 +1093,69 @@ pub fn rightmost(obstacle_list: &[Box<dyn Obstacle>]) -> i16 {
         .unwrap_or(0)
 }
 
+// What touching an obstacle means, it's up to the walk to act on it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Contact {
+    // Can be landed on from above, anything else knocks the boy out. The
+    // normal is the face of the platform he hit.
+    Solid { top: i32, normal: Normal },
+    // Solid, but landing on it launches the boy
+    Springboard { top: i32, normal: Normal },
+    Lethal,
+    Collectible { score: u32 },
+    PowerUp(PowerUp),
+    Trigger(Trigger),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trigger {
+    SegmentCleared,
+}
+
+pub trait Obstacle {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact>;
+    fn draw(&self, renderer: &Renderer);
+    fn move_horizontally(&mut self, x: i32);
+    fn right(&self) -> i32;
+    // What `contact` checks against, for the debug overlay
+    fn hitboxes(&self) -> Vec<Rect>;
+    // What it is, for the debug overlay's collision log and the game over panel
+    fn name(&self) -> &'static str;
+    fn layer(&self) -> Layers;
+
+    // All of the hitboxes together, for the broad phase
+    fn bounds(&self) -> Rect {
+        let hitboxes = self.hitboxes();
+        let left = hitboxes.iter().map(Rect::left).min().unwrap_or(0);
+        let right = hitboxes.iter().map(Rect::right).max().unwrap_or(0);
+        let top = hitboxes.iter().map(Rect::top).min().unwrap_or(0);
+        let bottom = hitboxes.iter().map(Rect::bottom).max().unwrap_or(0);
+        Rect::new_from_x_y(left, top, right - left, bottom - top)
+    }
+
+    // Collectibles and triggers are consumed on contact and then removed
+    fn consume(&mut self) {}
+
+    fn consumed(&self) -> bool {
+        false
+    }
+
+    // Only collectibles are pulled in by the magnet
+    fn attract(&mut self, _target: &Point) {}
+
+    // Called every tick after the world has scrolled, for obstacles that move
+    // or animate on their own
+    fn update(&mut self) {}
+
+    // The boy just landed on it
+    fn stand_on(&mut self) {}
+
+    // Turns a pooled obstacle into a new one for `piece`, if it's the same
+    // kind of obstacle, and returns whether it did. Every kind has to, or
+    // released ones would sit in the pool for good.
+    fn respawn(&mut self, piece: &Piece) -> bool;
+}
+
 pub struct Barrier {
     image: Image,
 }
@@ -722,9 +1167,16 @@ impl Barrier {
 }
 
 impl Obstacle for Barrier {
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if boy.bounding_box().intersects(self.image.bounding_box()) {
-            boy.knock_out();
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        sweep.impact(self.image.bounding_box())?;
+
+        // The stone's shape is only checked where he ends up. Going right
+        // through its box in one tick is a hit either way.
+        let end = sweep.to();
+        if !end.intersects(self.image.bounding_box()) || self.image.touches(&end) {
+            Some(Contact::Lethal)
+        } else {
+            None
         }
     }
 
@@ -732,37 +1184,139 @@ impl Obstacle for Barrier {
         self.image.draw(renderer);
     }
 
-    fn move_horizontally(&mut self, x: i16) {
+    fn move_horizontally(&mut self, x: i32) {
         self.image.move_horizontally(x);
     }
 
-    fn right(&self) -> i16 {
+    fn right(&self) -> i32 {
         self.image.bounding_box().right()
     }
+
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::Stone(position) => {
+                self.image.set_position(*position);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![*self.image.bounding_box()]
+    }
+
+    fn layer(&self) -> Layers {
+        HAZARD
+    }
+
+    fn name(&self) -> &'static str {
+        "stone"
+    }
 }
 
-pub struct Platform {
-    sheet: Rc<SpriteSheet>,
-    bounding_boxes: Vec<Rect>,
-    sprites: Vec<Cell>,
-    position: Point,
+// Hangs down from the top of the screen, too low to run under and too high to
+// jump over, so it has to be slid under
+pub struct Overhang {
+    bounding_box: Rect,
 }
 
-impl Platform {
-    pub fn new(
-        sheet: Rc<SpriteSheet>,
-        position: Point,
-        sprite_names: &[&str],
-        bounding_boxes: &[Rect],
-    ) -> Self {
-        let sprites = sprite_names
-            .iter()
-            // Cloned turns Option<&T> into Option<T>
-            .filter_map(|sprite_name| sheet.cell(sprite_name).cloned())
-            .collect();
+impl Overhang {
+    pub fn new(bounding_box: Rect) -> Self {
+        Overhang { bounding_box }
+    }
+}
+
+impl Obstacle for Overhang {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if sweep.impact(&self.bounding_box).is_some() {
+            Some(Contact::Lethal)
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect(&self.bounding_box, OVERHANG_COLOR);
+    }
+
+    fn move_horizontally(&mut self, x: i32) {
+        self.bounding_box.set_x(self.bounding_box.x() + x);
+    }
+
+    fn right(&self) -> i32 {
+        self.bounding_box.right()
+    }
+
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::Overhang(bounding_box) => {
+                self.bounding_box = *bounding_box;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![self.bounding_box]
+    }
+
+    fn layer(&self) -> Layers {
+        HAZARD
+    }
+
+    fn name(&self) -> &'static str {
+        "overhang"
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformKind {
+    Fixed,
+    // Bobs up and down once it's on screen
+    Moving,
+    // Gives way a moment after the boy lands on it
+    Crumbling,
+    // Launches the boy higher than he can jump
+    Springboard,
+}
+
+// How far a moving platform at `x` on screen is from where it was placed. Like
+// enemies it moves by how far the world has scrolled, so the reachability
+// check knows where it will be.
+pub fn moving_platform_offset(x: i32) -> i32 {
+    let ticks = (WIDTH - x).max(0) as f32 / RUNNING_SPEED as f32;
+    ((ticks * std::f32::consts::TAU / PLATFORM_PERIOD).sin() * PLATFORM_TRAVEL).round() as i32
+}
+
+pub struct Platform {
+    sheet: Rc<SpriteSheet>,
+    bounding_boxes: Vec<Rect>,
+    sprites: Vec<Cell>,
+    position: Point,
+    sprite_names: &'static [&'static str],
+    kind: PlatformKind,
+    resting_y: i32,
+    velocity_y: i32,
+    // Ticks until a crumbling platform falls, once it has been stood on
+    crumble: Option<u8>,
+}
+
+impl Platform {
+    pub fn new(
+        sheet: Rc<SpriteSheet>,
+        position: Point,
+        sprite_names: &'static [&'static str],
+        bounding_boxes: &[Rect],
+    ) -> Self {
+        let sprites = sprite_names
+            .iter()
+            // Cloned turns Option<&T> into Option<T>
+            .filter_map(|sprite_name| sheet.cell(sprite_name).cloned())
+            .collect();
 
         // We are making bounding boxes be referenced by their image
-        // This will screw up my draw_rect
         let bounding_boxes = bounding_boxes
             .iter()
             .map(|bounding_box| {
@@ -777,131 +1331,960 @@ impl Platform {
             bounding_boxes,
             sprites,
             position,
+            sprite_names,
+            kind: PlatformKind::Fixed,
+            resting_y: position.y,
+            velocity_y: 0,
+            crumble: None,
         }
     }
 
-    // pub fn draw_bounding_boxes(&self, renderer: &Renderer) {
-    //     for bounding_box in &self.bounding_boxes {
-    //         // TODO: this won't work anymore
-    //         renderer.draw_rect(bounding_box);
-    //     }
-    // }
+    pub fn with_kind(mut self, kind: PlatformKind) -> Self {
+        self.set_kind(kind);
+        self
+    }
 
-    pub fn bounding_boxes(&self) -> &Vec<Rect> {
-        &self.bounding_boxes
-        // const X_OFFSET: i16 = 60;
-        // const END_HEIGHT: i16 = 54;
-        // let destination_box = self.destination_box();
-        // let position = Point {
-        //     x: destination_box.x(),
-        //     y: destination_box.y(),
-        // };
-        // let bounding_box_one = Rect::new(position, X_OFFSET, END_HEIGHT);
-
-        // let position = Point {
-        //     x: destination_box.x() + X_OFFSET,
-        //     y: destination_box.y(),
-        // };
-        // let width = destination_box.width - (X_OFFSET * 2);
-        // let bounding_box_two = Rect::new(position, width, destination_box.height);
-
-        // let position = Point {
-        //     x: destination_box.x() + destination_box.width - X_OFFSET,
-        //     y: destination_box.y(),
-        // };
-        // let bounding_box_three = Rect::new(position, X_OFFSET, END_HEIGHT);
-
-        // vec![bounding_box_one, bounding_box_two, bounding_box_three]
-    }
-
-    // could delete but this is still used by check_intersection
-    pub fn destination_box(&self) -> Rect {
-        let platform = self.current_sprite().expect("13.png does not exist");
+    fn set_kind(&mut self, kind: PlatformKind) {
+        self.kind = kind;
+        if kind == PlatformKind::Moving {
+            self.move_vertically(moving_platform_offset(self.position.x));
+        }
+    }
 
-        let position = Point {
-            x: self.position.x,
-            y: self.position.y,
-        };
-        let width = (platform.frame.w * 3) as i16;
-        let height = platform.frame.h as i16;
-        Rect::new(position, width, height)
+    fn move_vertically(&mut self, y: i32) {
+        self.position.y += y;
+        self.bounding_boxes.iter_mut().for_each(|bounding_box| {
+            bounding_box.position.y += y;
+        });
     }
 
-    pub fn current_sprite(&self) -> Option<&Cell> {
-        self.sheet.cell("13.png")
+    fn crumbled(&self) -> bool {
+        self.crumble == Some(0)
     }
-}
 
-pub trait Obstacle {
-    fn check_intersection(&self, boy: &mut RedHatBoy);
-    fn draw(&self, renderer: &Renderer);
-    fn move_horizontally(&mut self, x: i16);
-    fn right(&self) -> i16;
+    pub fn bounding_boxes(&self) -> &Vec<Rect> {
+        &self.bounding_boxes
+    }
+
+    // Whether any solid pixel of the tiles is inside `rect`. A tile the sheet
+    // has no mask for is solid all over.
+    fn touches(&self, rect: &Rect) -> bool {
+        let mut x = self.position.x;
+        self.sprite_names.iter().any(|name| {
+            let cell = match self.sheet.cell(name) {
+                Some(cell) => cell,
+                None => return false,
+            };
+            let tile =
+                Rect::new_from_x_y(x, self.position.y, cell.frame.w as i32, cell.frame.h as i32);
+            x += tile.width();
+            rect.intersects(&tile)
+                && match self.sheet.mask(name) {
+                    Some(mask) => mask.overlaps(tile.position, rect),
+                    None => true,
+                }
+        })
+    }
 }
 
 impl Obstacle for Platform {
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if let Some(box_to_land_on) = self
-            .bounding_boxes()
-            .iter()
-            .find(|&bounding_box| boy.bounding_box().intersects(bounding_box))
-        {
-            // remember positive velocity means going down
-            // and if y1 < y2 it means that y1 is above y2
-            let is_falling = boy.velocity_y() > 0;
-            let is_above_platform = boy.pos_y() < self.destination_box().y();
-
-            if is_falling && is_above_platform {
-                let position = box_to_land_on.y();
-                boy.land_on(position);
-            } else {
-                boy.knock_out();
-            }
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if self.crumbled() {
+            return None;
         }
+
+        // Seen from the platform, which may have moved too. The first box
+        // reached is the one hit, so the seams between boxes can't trip him.
+        let sweep = sweep.relative_to(Point {
+            x: 0,
+            y: self.velocity_y,
+        });
+        self.bounding_boxes()
+            .iter()
+            .filter_map(|platform_box| Some((platform_box, sweep.impact(platform_box)?)))
+            .min_by_key(|(_, impact)| impact.time)
+            // Landing stays on the box, so a soft top edge can't drop him
+            // through. Running into a side or the underside only counts if
+            // he reaches the tiles themselves.
+            .filter(|(_, impact)| impact.normal == Normal::Up || self.touches(&sweep.to()))
+            .map(|(box_to_land_on, impact)| match self.kind {
+                PlatformKind::Springboard => Contact::Springboard {
+                    top: box_to_land_on.y(),
+                    normal: impact.normal,
+                },
+                _ => Contact::Solid {
+                    top: box_to_land_on.y(),
+                    normal: impact.normal,
+                },
+            })
     }
 
     fn draw(&self, renderer: &Renderer) {
-        let mut x = 0;
+        // A platform about to crumble shakes
+        let shake = match self.crumble {
+            Some(ticks) if ticks > 0 => i32::from(ticks % 2) * 2 - 1,
+            _ => 0,
+        };
+        let mut x = shake;
         self.sprites.iter().for_each(|sprite| {
-            let rect_x = sprite.frame.x as i16;
-            let rect_y = sprite.frame.y as i16;
-            let width = sprite.frame.w as i16;
-            let height = sprite.frame.h as i16;
+            let rect_x = sprite.frame.x as i32;
+            let rect_y = sprite.frame.y as i32;
+            let width = sprite.frame.w as i32;
+            let height = sprite.frame.h as i32;
             let source = Rect::new_from_x_y(rect_x, rect_y, width, height);
 
             let rect_x = self.position.x + x;
             let rect_y = self.position.y;
-            let width = sprite.frame.w as i16;
-            let height = sprite.frame.h as i16;
+            let width = sprite.frame.w as i32;
+            let height = sprite.frame.h as i32;
             let destination = Rect::new_from_x_y(rect_x, rect_y, width, height);
 
             self.sheet.draw(renderer, &source, &destination);
 
-            x += sprite.frame.w as i16;
+            x += sprite.frame.w as i32;
         });
 
-        // let platform = self.current_sprite().expect("13.png does not exist");
-
-        // let destination = self.destination_box();
-
-        // let position = Point {
-        //     x: platform.frame.x as i16,
-        //     y: platform.frame.y as i16,
-        // };
-        // let source = Rect::new(position, destination.width, destination.height);
-
-        // self.sheet.draw(renderer, &source, &destination);
-        // self.draw_bounding_boxes(renderer);
+        if self.kind == PlatformKind::Springboard {
+            let pad = Rect::new_from_x_y(self.position.x, self.position.y, x, SPRINGBOARD_PAD);
+            renderer.fill_rect(&pad, SPRINGBOARD_COLOR);
+        }
     }
 
-    fn move_horizontally(&mut self, x: i16) {
+    fn move_horizontally(&mut self, x: i32) {
         self.position.x += x;
         self.bounding_boxes.iter_mut().for_each(|bounding_box| {
             bounding_box.set_x(bounding_box.position.x + x);
         });
     }
 
-    fn right(&self) -> i16 {
+    fn update(&mut self) {
+        match self.kind {
+            PlatformKind::Moving => {
+                let y = self.resting_y + moving_platform_offset(self.position.x);
+                self.velocity_y = y - self.position.y;
+                self.move_vertically(self.velocity_y);
+            }
+            PlatformKind::Crumbling if self.crumbled() => {
+                self.velocity_y = (self.velocity_y + CRUMBLE_GRAVITY).min(CRUMBLE_FALL_SPEED);
+                self.move_vertically(self.velocity_y);
+            }
+            PlatformKind::Crumbling => {
+                self.crumble = self.crumble.map(|ticks| ticks - 1);
+            }
+            PlatformKind::Fixed | PlatformKind::Springboard => {}
+        }
+    }
+
+    // Any platform made of the same tiles can be moved to where it's needed
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece.platform() {
+            Some((position, sprite_names, kind)) if sprite_names == self.sprite_names => {
+                self.move_horizontally(position.x - self.position.x);
+                self.move_vertically(position.y - self.position.y);
+                self.resting_y = position.y;
+                self.velocity_y = 0;
+                self.crumble = None;
+                self.set_kind(kind);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn stand_on(&mut self) {
+        if self.kind == PlatformKind::Crumbling && self.crumble.is_none() {
+            self.crumble = Some(CRUMBLE_TICKS);
+        }
+    }
+
+    // Once a crumbled platform has fallen off the screen it's gone
+    fn consumed(&self) -> bool {
+        self.crumbled() && self.position.y > HEIGHT
+    }
+
+    fn right(&self) -> i32 {
         self.bounding_boxes.last().unwrap().right()
     }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        self.bounding_boxes.clone()
+    }
+
+    fn layer(&self) -> Layers {
+        SOLID
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            PlatformKind::Springboard => "springboard",
+            _ => "platform",
+        }
+    }
+}
+
+const COIN_RADIUS: i32 = 12;
+const COIN_SCORE: u32 = 10;
+const COIN_COLOR: &str = "#FFD700";
+
+pub struct Coin {
+    center: Point,
+    collected: bool,
+}
+
+impl Coin {
+    pub fn new(center: Point) -> Self {
+        Coin {
+            center,
+            collected: false,
+        }
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect::new_from_x_y(
+            self.center.x - COIN_RADIUS,
+            self.center.y - COIN_RADIUS,
+            COIN_RADIUS * 2,
+            COIN_RADIUS * 2,
+        )
+    }
+}
+
+impl Obstacle for Coin {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if sweep.impact(&self.bounding_box()).is_some() {
+            Some(Contact::Collectible { score: COIN_SCORE })
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_circle(&self.center, COIN_RADIUS, COIN_COLOR);
+    }
+
+    fn move_horizontally(&mut self, x: i32) {
+        self.center.x += x;
+    }
+
+    fn right(&self) -> i32 {
+        self.bounding_box().right()
+    }
+
+    fn consume(&mut self) {
+        self.collected = true;
+    }
+
+    fn consumed(&self) -> bool {
+        self.collected
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![self.bounding_box()]
+    }
+
+    fn layer(&self) -> Layers {
+        PICKUP
+    }
+
+    fn name(&self) -> &'static str {
+        "coin"
+    }
+
+    fn attract(&mut self, target: &Point) {
+        power_ups::attract(&mut self.center, target);
+    }
+
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::Coin(center) => {
+                *self = Coin::new(*center);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// An invisible area that fires once when the boy walks into it
+pub struct TriggerZone {
+    bounding_box: Rect,
+    trigger: Trigger,
+    fired: bool,
+}
+
+impl TriggerZone {
+    pub fn new(bounding_box: Rect, trigger: Trigger) -> Self {
+        TriggerZone {
+            bounding_box,
+            trigger,
+            fired: false,
+        }
+    }
+}
+
+impl Obstacle for TriggerZone {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if sweep.impact(&self.bounding_box).is_some() {
+            Some(Contact::Trigger(self.trigger))
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, _renderer: &Renderer) {}
+
+    fn move_horizontally(&mut self, x: i32) {
+        self.bounding_box.set_x(self.bounding_box.x() + x);
+    }
+
+    fn right(&self) -> i32 {
+        self.bounding_box.right()
+    }
+
+    fn consume(&mut self) {
+        self.fired = true;
+    }
+
+    fn consumed(&self) -> bool {
+        self.fired
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![self.bounding_box]
+    }
+
+    fn layer(&self) -> Layers {
+        TRIGGER
+    }
+
+    fn name(&self) -> &'static str {
+        "trigger"
+    }
+
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::Trigger(bounding_box, trigger) => {
+                *self = TriggerZone::new(*bounding_box, *trigger);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_bindgen::JsValue;
+    use web_sys::AudioBuffer;
+
+    fn running_boy() -> RedHatBoyStateMachine {
+        let jump_sound = Sound {
+            buffer: AudioBuffer::from(JsValue::NULL),
+        };
+        let idle = RedHatBoyState::new(Rc::new(Audio::silent()), Rc::new(jump_sound));
+        RedHatBoyStateMachine::Idle(idle).transition(Event::Run)
+    }
+
+    fn boy() -> RedHatBoy {
+        boy_with_lives(Some(STARTING_LIVES))
+    }
+
+    fn boy_with_lives(lives: Option<u8>) -> RedHatBoy {
+        let mut boy = idle_boy(lives);
+        boy.run_right();
+        boy
+    }
+
+    fn idle_boy(lives: Option<u8>) -> RedHatBoy {
+        RedHatBoy::new(
+            Sheet::default(),
+            HtmlImageElement::from(JsValue::NULL),
+            Audio::silent(),
+            Sound {
+                buffer: AudioBuffer::from(JsValue::NULL),
+            },
+            lives,
+        )
+    }
+
+    const FRESH_JUMP: Controls = Controls {
+        jump: true,
+        fresh_jump: true,
+        slide: false,
+    };
+
+    fn jumping_boy() -> RedHatBoyStateMachine {
+        match running_boy() {
+            RedHatBoyStateMachine::Running(state) => {
+                RedHatBoyStateMachine::Jumping(state.take_off())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // How many ticks a slide lasts, holding down throughout or only pressing it once
+    fn slide_length(boy: RedHatBoyStateMachine, held: bool) -> u8 {
+        let mut boy = boy.transition(Event::Slide);
+        let mut ticks = 0;
+        while matches!(boy, RedHatBoyStateMachine::Sliding(_)) {
+            let input = if held {
+                Event::Slide
+            } else {
+                Event::ReleaseSlide
+            };
+            boy = boy.transition(input).transition(Event::Update);
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn a_tapped_slide_lasts_the_whole_animation() {
+        assert_eq!(slide_length(running_boy(), false), SLIDING_FRAMES);
+    }
+
+    #[test]
+    fn a_held_slide_lasts_until_the_stamina_runs_out() {
+        assert_eq!(slide_length(running_boy(), true), SLIDE_STAMINA);
+    }
+
+    #[test]
+    fn an_exhausted_boy_cannot_slide_until_he_has_run_a_while() {
+        let mut boy = (0..SLIDE_STAMINA).fold(running_boy().transition(Event::Slide), |boy, _| {
+            boy.transition(Event::Slide).transition(Event::Update)
+        });
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+
+        boy = boy.transition(Event::Slide);
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+
+        boy = (0..SLIDING_FRAMES).fold(boy, |boy, _| boy.transition(Event::Update));
+        boy = boy.transition(Event::Slide);
+        assert!(matches!(boy, RedHatBoyStateMachine::Sliding(_)));
+    }
+
+    #[test]
+    fn pressing_down_in_the_air_falls_faster_than_terminal_velocity() {
+        let boy = jumping_boy().transition(Event::Slide);
+        assert_eq!(boy.context().velocity.y, FAST_FALL_SPEED);
+
+        let boy = boy.transition(Event::Update);
+        assert!(matches!(boy, RedHatBoyStateMachine::Jumping(_)));
+        assert!(boy.context().velocity.y > TERMINAL_VELOCITY);
+    }
+
+    #[test]
+    fn a_fast_fall_lands_like_any_other_jump() {
+        let boy = jumping_boy()
+            .transition(Event::Slide)
+            .transition(Event::Update)
+            .transition(Event::Land(GROUND_TOP));
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+        assert_eq!(boy.context().position.y, FLOOR.into());
+    }
+
+    #[test]
+    fn a_fractional_speed_scrolls_whole_pixels_without_drifting() {
+        let mut boy = match running_boy() {
+            RedHatBoyStateMachine::Running(mut state) => {
+                state.context.velocity.x = Fixed::ratio(9, 2);
+                RedHatBoyStateMachine::Running(state)
+            }
+            _ => unreachable!(),
+        };
+
+        let mut strides = vec![];
+        for _ in 0..10 {
+            boy = boy.transition(Event::Update);
+            strides.push(boy.context().stride);
+        }
+
+        assert_eq!(strides, vec![4, 5, 4, 5, 4, 5, 4, 5, 4, 5]);
+    }
+
+    #[test]
+    fn slow_motion_shortens_the_stride() {
+        let mut boy = running_boy();
+        boy.context_mut().slow_motion = true;
+
+        let strides: Vec<i32> = (0..4)
+            .map(|_| {
+                boy = boy.clone().transition(Event::Update);
+                boy.context().stride
+            })
+            .collect();
+
+        assert_eq!(strides, vec![2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn a_stone_can_be_cleared_in_slow_motion() {
+        // Whether jumping `gap` pixels before a stone gets him over it
+        let clears = |gap: i32| {
+            let mut boy = jumping_boy();
+            boy.context_mut().slow_motion = true;
+            let hitbox = |boy: &RedHatBoyStateMachine| {
+                hitboxes::hitbox_at(boy.frame_name(), boy.context().position.round()).unwrap()
+            };
+            let mut stone = Rect::new_from_x_y(hitbox(&boy).right() + gap, GROUND_TOP - 54, 90, 54);
+
+            for _ in 0..500 {
+                boy = boy.transition(Event::Update);
+                stone.set_x(stone.x() - boy.context().stride);
+                if hitbox(&boy).intersects(&stone) {
+                    return false;
+                }
+                if stone.right() < hitbox(&boy).left() {
+                    return true;
+                }
+                if boy.context().position.y >= FLOOR.into() {
+                    boy = boy.transition(Event::Land(GROUND_TOP));
+                }
+            }
+            false
+        };
+
+        assert!((0..200).any(clears));
+    }
+
+    #[test]
+    fn a_fresh_press_in_slow_motion_always_double_jumps() {
+        // However many ticks into the slow motion the press comes
+        (0..4).for_each(|wait| {
+            let mut boy = boy();
+            boy.power_up(PowerUp::SlowMotion);
+            boy.power_up(PowerUp::DoubleJump);
+            boy.control(FRESH_JUMP);
+            (0..wait).for_each(|_| boy.control(Controls::default()));
+            assert!(!boy.state_machine.context().air_jumped);
+
+            boy.control(FRESH_JUMP);
+
+            assert!(
+                boy.state_machine.context().air_jumped,
+                "No double jump after waiting {} ticks",
+                wait
+            );
+        });
+    }
+
+    #[test]
+    fn a_hit_with_lives_to_spare_hurts_the_boy() {
+        let mut boy = boy();
+
+        boy.hit();
+
+        assert!(matches!(boy.state_machine, RedHatBoyStateMachine::Hurt(_)));
+        assert_eq!(boy.lives(), Some(STARTING_LIVES - 1));
+        assert!(boy.power_ups().is_invulnerable());
+    }
+
+    #[test]
+    fn hits_during_the_grace_period_are_ignored() {
+        let mut boy = boy();
+        boy.hit();
+
+        (0..HURT_GRACE - 1).for_each(|_| {
+            boy.control(Controls::default());
+            boy.hit();
+        });
+        assert_eq!(boy.lives(), Some(STARTING_LIVES - 1));
+
+        boy.control(Controls::default());
+        boy.hit();
+        assert_eq!(boy.lives(), Some(STARTING_LIVES - 2));
+    }
+
+    #[test]
+    fn the_hurt_animation_ends_back_at_a_run() {
+        let hurt = running_boy().transition(Event::Hurt);
+        let boy = (0..HURT_FRAMES - 1).fold(hurt, |boy, _| boy.transition(Event::Update));
+        assert!(matches!(boy, RedHatBoyStateMachine::Hurt(_)));
+
+        let boy = boy.transition(Event::Update);
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn a_boy_hurt_in_the_air_lands_hurt_and_then_runs() {
+        let hurt = jumping_boy().transition(Event::Hurt);
+        assert!(matches!(hurt, RedHatBoyStateMachine::Hurt(_)));
+
+        let landed = (0..5)
+            .fold(hurt, |boy, _| boy.transition(Event::Update))
+            .transition(Event::Land(GROUND_TOP));
+        assert!(matches!(landed, RedHatBoyStateMachine::Hurt(_)));
+        assert_eq!(landed.context().position.y, FLOOR.into());
+
+        let boy = (5..HURT_FRAMES).fold(landed, |boy, _| boy.transition(Event::Update));
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn the_last_life_knocks_the_boy_out() {
+        let mut boy = boy_with_lives(Some(1));
+
+        boy.hit();
+
+        assert!(matches!(
+            boy.state_machine,
+            RedHatBoyStateMachine::Falling(_)
+        ));
+    }
+
+    #[test]
+    fn without_lives_the_first_hit_knocks_the_boy_out() {
+        let mut boy = boy_with_lives(None);
+
+        boy.hit();
+
+        assert!(matches!(
+            boy.state_machine,
+            RedHatBoyStateMachine::Falling(_)
+        ));
+        assert_eq!(boy.lives(), None);
+    }
+
+    #[test]
+    fn a_hit_he_cannot_be_hurt_by_costs_no_life() {
+        let mut boy = idle_boy(Some(STARTING_LIVES));
+
+        boy.hit();
+
+        assert!(matches!(boy.state_machine, RedHatBoyStateMachine::Idle(_)));
+        assert_eq!(boy.lives(), Some(STARTING_LIVES));
+        assert!(!boy.power_ups().is_invulnerable());
+    }
+
+    #[test]
+    fn an_invulnerable_boy_still_runs_into_walls() {
+        let mut boy = boy();
+        boy.control(Controls::default());
+        boy.power_ups.make_invulnerable(HURT_GRACE);
+        assert!(boy.walking_speed() > 0);
+
+        assert!(!boy.land_on_or_knock_out(GROUND_TOP, Normal::Left));
+
+        assert_eq!(boy.walking_speed(), 0);
+        assert_eq!(boy.lives(), Some(STARTING_LIVES));
+    }
+
+    #[test]
+    fn an_invulnerable_boy_still_bumps_his_head() {
+        let mut boy = boy();
+        boy.control(FRESH_JUMP);
+        boy.control(Controls {
+            jump: true,
+            ..Controls::default()
+        });
+        boy.power_ups.make_invulnerable(HURT_GRACE);
+        let before = boy.state_machine.context().last_position.y;
+        assert!(boy.velocity().y < Fixed::ZERO);
+
+        boy.land_on_or_knock_out(0, Normal::Down);
+
+        assert!(boy.velocity().y >= Fixed::ZERO);
+        assert_eq!(boy.state_machine.context().position.y, before);
+    }
+
+    #[test]
+    fn changing_the_lives_setting_waits_for_the_next_run() {
+        let mut boy = boy_with_lives(None);
+
+        boy.set_starting_lives(Some(STARTING_LIVES));
+
+        assert_eq!(boy.starting_lives(), Some(STARTING_LIVES));
+        assert_eq!(boy.lives(), None);
+    }
+
+    #[test]
+    fn every_animation_the_boy_can_be_in_has_a_hitbox() {
+        let running = running_boy();
+        let falling = running.clone().transition(Event::KnockOut);
+        let dead = (0..100).fold(falling.clone(), |boy, _| boy.transition(Event::Update));
+        assert!(dead.is_dead());
+        let states = [
+            RedHatBoyStateMachine::Idle(RedHatBoyState::new(
+                running.context().audio.clone(),
+                running.context().jump_sound.clone(),
+            )),
+            running.clone().transition(Event::Slide),
+            jumping_boy(),
+            running.clone().transition(Event::Hurt),
+            running,
+            falling,
+            dead,
+        ];
+
+        states.iter().for_each(|state| {
+            assert!(
+                hitboxes::hitbox_for(state.frame_name()).is_some(),
+                "No hitbox for {}",
+                state.frame_name()
+            );
+        });
+    }
+
+    #[test]
+    fn the_same_inputs_make_the_same_jump() {
+        let jump = || {
+            (0..40).fold(jumping_boy(), |boy, tick| match tick {
+                12 => boy.transition(Event::ReleaseJump),
+                _ => boy.transition(Event::Update),
+            })
+        };
+
+        let (first, second) = (jump(), jump());
+        assert_eq!(first.context().position, second.context().position);
+        assert_eq!(first.context().velocity, second.context().velocity);
+    }
+
+    #[test]
+    fn only_the_solid_part_of_a_stone_knocks_the_boy_out() {
+        // A 2x2 stone missing its top left corner
+        let pixels = [0, 255, 255, 255]
+            .iter()
+            .flat_map(|&alpha| vec![0, 0, 0, alpha])
+            .collect::<Vec<u8>>();
+        let stone = Barrier::new(
+            Image::sized(
+                HtmlImageElement::from(wasm_bindgen::JsValue::NULL),
+                Rect::new_from_x_y(100, 100, 2, 2),
+            )
+            .with_mask(Rc::new(CollisionMask::from_alpha(2, 2, &pixels))),
+        );
+        let boy_at =
+            |x, y| Sweep::ending_at(Rect::new_from_x_y(x, y, 10, 10), Point { x: 4, y: 0 });
+
+        assert_eq!(stone.contact(&boy_at(91, 91)), None);
+        assert_eq!(stone.contact(&boy_at(92, 91)), Some(Contact::Lethal));
+        // Straight through the stone in one tick
+        assert_eq!(
+            stone.contact(&Sweep::ending_at(
+                Rect::new_from_x_y(120, 95, 10, 10),
+                Point { x: 40, y: 0 }
+            )),
+            Some(Contact::Lethal)
+        );
+    }
+
+    #[test]
+    fn a_coin_is_collected_once() {
+        let mut coin = Coin::new(Point { x: 100, y: 100 });
+        let boy = Rect::new_from_x_y(90, 50, 40, 100);
+
+        assert_eq!(
+            coin.contact(&Sweep::at(boy)),
+            Some(Contact::Collectible { score: COIN_SCORE })
+        );
+
+        coin.consume();
+        assert!(coin.consumed());
+    }
+
+    fn platform(bounding_box: Rect, kind: PlatformKind) -> Platform {
+        masked_platform(bounding_box, kind, None)
+    }
+
+    // Made of one tile the size of its box
+    fn masked_platform(
+        bounding_box: Rect,
+        kind: PlatformKind,
+        mask: Option<CollisionMask>,
+    ) -> Platform {
+        let frame = SheetRect {
+            x: 0,
+            y: 0,
+            w: bounding_box.width() as u16,
+            h: bounding_box.height() as u16,
+        };
+        let cell = Cell {
+            frame: frame.clone(),
+            sprite_source_size: frame,
+        };
+        Platform {
+            sheet: Rc::new(SpriteSheet {
+                sheet: Sheet {
+                    frames: HashMap::from([("tile".to_string(), cell.clone())]),
+                },
+                image: HtmlImageElement::from(wasm_bindgen::JsValue::NULL),
+                masks: mask
+                    .into_iter()
+                    .map(|mask| ("tile".to_string(), mask))
+                    .collect(),
+            }),
+            bounding_boxes: vec![bounding_box],
+            sprites: vec![cell],
+            position: bounding_box.position,
+            sprite_names: &["tile"],
+            kind: PlatformKind::Fixed,
+            resting_y: bounding_box.y(),
+            velocity_y: 0,
+            crumble: None,
+        }
+        .with_kind(kind)
+    }
+
+    // A boy that ends the tick at `x, y`, having fallen `distance` to get there
+    fn falling_boy(x: i32, y: i32, distance: i32) -> Sweep {
+        Sweep::ending_at(
+            Rect::new_from_x_y(x, y, 40, 60),
+            Point { x: 0, y: distance },
+        )
+    }
+
+    #[test]
+    fn a_platform_reports_the_top_of_the_box_that_was_touched() {
+        let platform = platform(Rect::new_from_x_y(0, 0, 100, 20), PlatformKind::Fixed);
+
+        assert_eq!(
+            platform.contact(&falling_boy(10, -50, 20)),
+            Some(Contact::Solid {
+                top: 0,
+                normal: Normal::Up
+            })
+        );
+    }
+
+    #[test]
+    fn a_fast_fall_cannot_pass_through_a_thin_platform() {
+        let platform = platform(Rect::new_from_x_y(0, 0, 100, 4), PlatformKind::Fixed);
+
+        assert_eq!(
+            platform.contact(&falling_boy(10, 10, 80)),
+            Some(Contact::Solid {
+                top: 0,
+                normal: Normal::Up
+            })
+        );
+    }
+
+    #[test]
+    fn running_into_the_side_of_a_platform_is_not_landing() {
+        let platform = platform(Rect::new_from_x_y(0, 0, 100, 20), PlatformKind::Fixed);
+        let boy = Sweep::ending_at(Rect::new_from_x_y(-38, -40, 40, 60), Point { x: 4, y: 1 });
+
+        assert_eq!(
+            platform.contact(&boy),
+            Some(Contact::Solid {
+                top: 0,
+                normal: Normal::Left
+            })
+        );
+    }
+
+    #[test]
+    fn only_the_solid_part_of_a_tile_stops_the_boy() {
+        // The first ten columns are see-through
+        let pixels: Vec<u8> = (0..20)
+            .flat_map(|_| (0..100).flat_map(|x| [0, 0, 0, if x < 10 { 0 } else { 255 }]))
+            .collect();
+        let mask = CollisionMask::from_alpha(100, 20, &pixels);
+        let platform = masked_platform(
+            Rect::new_from_x_y(0, 0, 100, 20),
+            PlatformKind::Fixed,
+            Some(mask),
+        );
+
+        let grazing = Sweep::ending_at(Rect::new_from_x_y(-38, -40, 40, 60), Point { x: 4, y: 1 });
+        assert_eq!(platform.contact(&grazing), None);
+
+        let running_in =
+            Sweep::ending_at(Rect::new_from_x_y(-28, -40, 40, 60), Point { x: 14, y: 0 });
+        assert_eq!(
+            platform.contact(&running_in),
+            Some(Contact::Solid {
+                top: 0,
+                normal: Normal::Left
+            })
+        );
+    }
+
+    #[test]
+    fn a_platform_far_from_the_origin_still_holds_the_boy_up() {
+        let far = i32::MAX - 10_000;
+        let mut platform = platform(Rect::new_from_x_y(far, far, 100, 20), PlatformKind::Fixed);
+
+        platform.move_horizontally(-RUNNING_SPEED);
+        assert_eq!(platform.right(), far + 100 - RUNNING_SPEED);
+
+        let left = far - RUNNING_SPEED;
+        assert_eq!(
+            platform.contact(&falling_boy(left + 10, far - 50, 20)),
+            Some(Contact::Solid {
+                top: far,
+                normal: Normal::Up
+            })
+        );
+        let running_in = Sweep::ending_at(
+            Rect::new_from_x_y(left - 38, far - 40, 40, 60),
+            Point { x: 4, y: 1 },
+        );
+        assert_eq!(
+            platform.contact(&running_in),
+            Some(Contact::Solid {
+                top: far,
+                normal: Normal::Left
+            })
+        );
+    }
+
+    #[test]
+    fn a_platform_rising_under_the_boy_catches_him() {
+        let mut platform = platform(Rect::new_from_x_y(WIDTH, 0, 100, 20), PlatformKind::Moving);
+        let mut before = 0;
+        for _ in 0..200 {
+            before = platform.bounding_boxes()[0].y();
+            platform.move_horizontally(-RUNNING_SPEED);
+            platform.update();
+            if platform.velocity_y < 0 {
+                break;
+            }
+        }
+
+        let after = platform.bounding_boxes()[0].y();
+        assert!(after < before);
+
+        // Standing still just above where the platform was
+        let boy = Rect::new_from_x_y(platform.bounding_boxes()[0].x(), before - 60, 40, 60);
+        assert_eq!(
+            platform.contact(&Sweep::at(boy)),
+            Some(Contact::Solid {
+                top: after,
+                normal: Normal::Up
+            })
+        );
+    }
+
+    #[test]
+    fn a_crumbling_platform_gives_way_after_being_stood_on() {
+        let bounding_box = Rect::new_from_x_y(0, 0, 100, 20);
+        let mut platform = platform(bounding_box, PlatformKind::Crumbling);
+
+        (0..100).for_each(|_| platform.update());
+        assert!(platform.contact(&Sweep::at(bounding_box)).is_some());
+
+        platform.stand_on();
+        (0..CRUMBLE_TICKS).for_each(|_| platform.update());
+        assert!(platform.contact(&Sweep::at(bounding_box)).is_none());
+
+        (0..100).for_each(|_| platform.update());
+        assert!(platform.consumed());
+    }
+
+    #[test]
+    fn a_springboard_is_not_just_solid() {
+        let bounding_box = Rect::new_from_x_y(0, 0, 100, 20);
+        let springboard = platform(bounding_box, PlatformKind::Springboard);
+
+        assert_eq!(
+            springboard.contact(&falling_boy(10, -50, 20)),
+            Some(Contact::Springboard {
+                top: 0,
+                normal: Normal::Up
+            })
+        );
+    }
 }
@@ -0,0 +1,283 @@
+use crate::segments::Piece;
+
+use super::Obstacle;
+
+// More than any one segment needs of any one kind of obstacle
+const POOL_CAPACITY: usize = 64;
+
+// Obstacles that are done with, kept to be turned into new ones instead of
+// allocating a new box for every piece of every segment
+#[derive(Default)]
+pub struct ObstaclePool {
+    free: Vec<Box<dyn Obstacle>>,
+}
+
+impl ObstaclePool {
+    pub fn release(&mut self, obstacle: Box<dyn Obstacle>) {
+        if self.free.len() < POOL_CAPACITY {
+            self.free.push(obstacle);
+        }
+    }
+
+    pub fn spawn(&mut self, piece: &Piece) -> Option<Box<dyn Obstacle>> {
+        let index = self
+            .free
+            .iter_mut()
+            .position(|obstacle| obstacle.respawn(piece))?;
+        Some(self.free.swap_remove(index))
+    }
+
+    // Moves anything consumed or scrolled off the left of the screen out of
+    // `obstacles` and into the pool, keeping the rest in order. Done in place,
+    // it runs every tick.
+    pub fn cull(&mut self, obstacles: &mut Vec<Box<dyn Obstacle>>) {
+        let mut kept = 0;
+        for index in 0..obstacles.len() {
+            if !obstacles[index].consumed() && obstacles[index].right() >= 0 {
+                obstacles.swap(kept, index);
+                kept += 1;
+            }
+        }
+        obstacles
+            .drain(kept..)
+            .for_each(|obstacle| self.release(obstacle));
+    }
+
+    pub fn release_all(&mut self, obstacles: &mut Vec<Box<dyn Obstacle>>) {
+        obstacles
+            .drain(..)
+            .for_each(|obstacle| self.free.push(obstacle));
+        self.free.truncate(POOL_CAPACITY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, rc::Rc, time::Instant};
+
+    use wasm_bindgen::JsValue;
+    use web_sys::HtmlImageElement;
+
+    use super::*;
+    use crate::{
+        engine::{Image, Point, Rect, SpriteSheet, Sweep},
+        game::{
+            hitboxes::hitbox_at,
+            red_hat_boy_states::{FLOOR, RUNNING_SPEED, STARTING_POINT},
+            scroll_obstacles, Barrier, Overhang, Sheet, WIDTH,
+        },
+        segments::{Segment, SEGMENTS},
+    };
+
+    // Stones need a loaded image to copy, which there isn't outside a browser
+    // Enough for every stone on screen at once
+    const STONES: usize = 8;
+    const WITHOUT_STONES: [Segment; 8] = [
+        Segment::Pit,
+        Segment::Overhang,
+        Segment::Walker,
+        Segment::Jumper,
+        Segment::Flyer,
+        Segment::MovingPlatform,
+        Segment::Springboard,
+        Segment::OpenGround,
+    ];
+
+    // The obstacle side of the Walking scene's update, with the boy
+    // running along the ground and nothing ever collected
+    struct World {
+        course: &'static [Segment],
+        obstacles: Vec<Box<dyn Obstacle>>,
+        pool: ObstaclePool,
+        sheet: Rc<SpriteSheet>,
+        stone: Image,
+        timeline: i32,
+        segments: usize,
+    }
+
+    impl World {
+        fn new() -> Self {
+            World::running(&WITHOUT_STONES)
+        }
+
+        // Stones can only come out of the pool, so it starts with a few
+        fn running(course: &'static [Segment]) -> Self {
+            let stone = || {
+                Box::new(Barrier::new(Image::sized(
+                    HtmlImageElement::from(JsValue::NULL),
+                    Rect::new_from_x_y(0, 0, 90, 54),
+                ))) as Box<dyn Obstacle>
+            };
+            let mut pool = ObstaclePool::default();
+            (0..STONES).for_each(|_| pool.release(stone()));
+
+            World {
+                course,
+                obstacles: vec![],
+                pool,
+                sheet: Rc::new(SpriteSheet {
+                    sheet: Sheet {
+                        frames: HashMap::new(),
+                    },
+                    image: HtmlImageElement::from(JsValue::NULL),
+                    masks: HashMap::new(),
+                }),
+                stone: Image::sized(
+                    HtmlImageElement::from(JsValue::NULL),
+                    Rect::new_from_x_y(0, 0, 0, 0),
+                ),
+                timeline: 0,
+                segments: 0,
+            }
+        }
+
+        fn tick(&mut self) -> usize {
+            let boy = Sweep::at(
+                hitbox_at(
+                    "Run",
+                    Point {
+                        x: STARTING_POINT,
+                        y: FLOOR,
+                    },
+                )
+                .unwrap(),
+            );
+            let course = self.course;
+            let segments = &mut self.segments;
+            let stone = &self.stone;
+            let sheet = &self.sheet;
+            scroll_obstacles(
+                &mut self.obstacles,
+                &mut self.pool,
+                &mut self.timeline,
+                -RUNNING_SPEED,
+                boy.from.left(),
+                |timeline, pool| {
+                    let segment = course[*segments % course.len()];
+                    *segments += 1;
+                    segment.obstacles(stone, sheet.clone(), timeline, pool)
+                },
+            );
+            self.obstacles
+                .iter()
+                .filter(|obstacle| obstacle.contact(&boy).is_some())
+                .count()
+        }
+    }
+
+    #[test]
+    fn a_long_run_only_keeps_what_is_on_screen() {
+        let mut world = World::new();
+
+        let most = (0..20_000)
+            .map(|_| {
+                world.tick();
+                world.obstacles.len()
+            })
+            .max()
+            .unwrap_or(0);
+
+        assert!(world.segments > 50);
+        assert!(most < 40, "{}", most);
+    }
+
+    // Far more scrolling than an i16 could ever hold, yet everything is
+    // measured from the screen so it never drifts away from the origin
+    #[test]
+    fn a_very_long_run_stays_near_the_origin() {
+        let mut world = World::new();
+
+        for _ in 0..100_000 {
+            world.tick();
+            assert!(world.timeline < WIDTH * 4, "{}", world.timeline);
+            world.obstacles.iter().for_each(|obstacle| {
+                assert!(obstacle.right() >= 0);
+                assert!(obstacle.right() < WIDTH * 4, "{}", obstacle.right());
+            });
+        }
+    }
+
+    // Every kind of obstacle, pickups included, is taken back out of the pool,
+    // so it never fills up with ones that are no use
+    #[test]
+    fn a_long_run_over_every_segment_keeps_reusing_obstacles() {
+        let mut world = World::running(&SEGMENTS);
+
+        for _ in 0..50_000 {
+            world.tick();
+            let pickups = world
+                .pool
+                .free
+                .iter()
+                .filter(|obstacle| obstacle.name() == "power up")
+                .count();
+            assert!(pickups <= 2, "{} pickups in the pool", pickups);
+        }
+
+        assert!(world.segments > 100);
+        assert!(world.pool.free.len() < POOL_CAPACITY);
+    }
+
+    #[test]
+    fn culling_keeps_the_rest_in_order() {
+        let mut pool = ObstaclePool::default();
+        let mut obstacles: Vec<Box<dyn Obstacle>> = [-50, 10, -30, 20, 30]
+            .iter()
+            .map(|&x| {
+                Box::new(Overhang::new(Rect::new_from_x_y(x, 0, 20, 20))) as Box<dyn Obstacle>
+            })
+            .collect();
+
+        pool.cull(&mut obstacles);
+
+        let kept: Vec<i32> = obstacles.iter().map(|obstacle| obstacle.right()).collect();
+        assert_eq!(kept, vec![30, 40, 50]);
+        assert_eq!(pool.free.len(), 2);
+    }
+
+    #[test]
+    fn obstacles_that_scroll_away_are_reused() {
+        let world = World::new();
+        let mut pool = ObstaclePool::default();
+        let mut first =
+            Segment::Overhang.obstacles(&world.stone, world.sheet.clone(), 0, &mut pool);
+        let count = first.len();
+
+        pool.release_all(&mut first);
+        assert_eq!(pool.free.len(), count);
+
+        let second =
+            Segment::Overhang.obstacles(&world.stone, world.sheet.clone(), 1000, &mut pool);
+        assert_eq!(second.len(), count);
+        assert_eq!(pool.free.len(), 0);
+    }
+
+    // Not a correctness test. Run it with
+    // cargo test --release bench -- --ignored --nocapture
+    // to see how long a tick takes early in a run and much later on.
+    #[test]
+    #[ignore]
+    fn bench_update_cost_stays_flat() {
+        const SAMPLE: u32 = 10_000;
+        let mut world = World::new();
+        let sample = |world: &mut World| {
+            let start = Instant::now();
+            (0..SAMPLE).for_each(|_| {
+                world.tick();
+            });
+            start.elapsed() / SAMPLE
+        };
+
+        let early = sample(&mut world);
+        (0..50).for_each(|_| {
+            sample(&mut world);
+        });
+        let late = sample(&mut world);
+
+        println!(
+            "{:?} per tick early, {:?} per tick after {} segments",
+            early, late, world.segments
+        );
+        assert!(late < early * 2, "{:?} then {:?}", early, late);
+    }
+}
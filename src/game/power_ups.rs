@@ -0,0 +1,313 @@
+use rand::prelude::*;
+
+use crate::{
+    engine::{broad_phase::Layers, Point, Rect, Renderer, Sweep},
+    segments::Piece,
+};
+
+use super::{Contact, Obstacle, PICKUP};
+
+const SECOND: u16 = 60;
+const PICKUP_RADIUS: i32 = 16;
+const HUD_LINE_HEIGHT: i32 = 20;
+const TEXT_OFFSET: Point = Point { x: -5, y: 5 };
+pub const MAGNET_RADIUS: i32 = 250;
+const MAGNET_PULL: i32 = 6;
+// Ticks of invulnerability after the shield breaks, so the boy can get clear of what broke it
+pub const SHIELD_GRACE: u16 = 45;
+// How many slow motion ticks make one ordinary tick
+pub const SLOW_MOTION_FACTOR: i32 = 2;
+const SHIELD_RADIUS: i32 = 80;
+const SHIELD_COLOR: &str = "rgba(80, 160, 255, 0.8)";
+const MAGNET_COLOR: &str = "rgba(230, 60, 60, 0.4)";
+const SLOW_MOTION_TINT: &str = "rgba(120, 80, 200, 0.15)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUp {
+    Shield,
+    Magnet,
+    DoubleJump,
+    SlowMotion,
+}
+
+pub const POWER_UPS: [PowerUp; 4] = [
+    PowerUp::Shield,
+    PowerUp::Magnet,
+    PowerUp::DoubleJump,
+    PowerUp::SlowMotion,
+];
+
+impl PowerUp {
+    fn duration(&self) -> u16 {
+        match self {
+            PowerUp::Shield => 15 * SECOND,
+            PowerUp::Magnet => 10 * SECOND,
+            PowerUp::DoubleJump => 10 * SECOND,
+            PowerUp::SlowMotion => 5 * SECOND,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            PowerUp::Shield => "Shield",
+            PowerUp::Magnet => "Magnet",
+            PowerUp::DoubleJump => "Double Jump",
+            PowerUp::SlowMotion => "Slow Motion",
+        }
+    }
+
+    pub fn color(&self) -> &str {
+        match self {
+            PowerUp::Shield => "rgba(80, 160, 255, 0.6)",
+            PowerUp::Magnet => "rgba(230, 60, 60, 0.6)",
+            PowerUp::DoubleJump => "rgba(90, 200, 90, 0.6)",
+            PowerUp::SlowMotion => "rgba(170, 110, 230, 0.6)",
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            PowerUp::Shield => "S",
+            PowerUp::Magnet => "M",
+            PowerUp::DoubleJump => "J",
+            PowerUp::SlowMotion => "T",
+        }
+    }
+}
+
+// Ticks left on every power up, counted in real time even during slow motion
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PowerUps {
+    remaining: [u16; 4],
+    invulnerable: u16,
+}
+
+impl PowerUps {
+    pub fn activate(&mut self, power_up: PowerUp) {
+        self.remaining[power_up as usize] = power_up.duration();
+    }
+
+    pub fn end(&mut self, power_up: PowerUp) {
+        self.remaining[power_up as usize] = 0;
+    }
+
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.remaining[power_up as usize] > 0
+    }
+
+    pub fn seconds_left(&self, power_up: PowerUp) -> u16 {
+        let remaining = self.remaining[power_up as usize];
+        remaining / SECOND + u16::from(remaining % SECOND > 0)
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = PowerUp> + '_ {
+        POWER_UPS
+            .iter()
+            .copied()
+            .filter(move |power_up| self.is_active(*power_up))
+    }
+
+    pub fn make_invulnerable(&mut self, ticks: u16) {
+        self.invulnerable = ticks;
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable > 0
+    }
+
+    // Used to blink the boy while he can't be hit
+    pub fn is_blinking(&self) -> bool {
+        self.is_invulnerable() && self.invulnerable & 4 == 0
+    }
+
+    pub fn draw_effects(&self, renderer: &Renderer, boy: &Rect, screen: &Rect) {
+        if self.is_active(PowerUp::SlowMotion) {
+            renderer.fill_rect(screen, SLOW_MOTION_TINT);
+        }
+
+        if self.is_active(PowerUp::Magnet) {
+            renderer.stroke_circle(&boy.center(), MAGNET_RADIUS, MAGNET_COLOR);
+        }
+
+        if self.is_active(PowerUp::Shield) {
+            renderer.stroke_circle(&boy.center(), SHIELD_RADIUS, SHIELD_COLOR);
+        }
+    }
+
+    pub fn draw_timers(&self, renderer: &Renderer, position: &Point) {
+        self.active().enumerate().for_each(|(line, power_up)| {
+            let text = format!("{} {}s", power_up.name(), self.seconds_left(power_up));
+            let location = Point {
+                x: position.x,
+                y: position.y + line as i32 * HUD_LINE_HEIGHT,
+            };
+            if let Err(err) = renderer.draw_text(&text, &location) {
+                error!("Could not draw power up timer {:#?}", err);
+            }
+        });
+    }
+
+    pub fn tick(&mut self) {
+        self.remaining
+            .iter_mut()
+            .for_each(|remaining| *remaining = remaining.saturating_sub(1));
+        self.invulnerable = self.invulnerable.saturating_sub(1);
+    }
+}
+
+pub struct PowerUpPickup {
+    center: Point,
+    power_up: PowerUp,
+    collected: bool,
+}
+
+impl PowerUpPickup {
+    pub fn new(center: Point, power_up: PowerUp) -> Self {
+        PowerUpPickup {
+            center,
+            power_up,
+            collected: false,
+        }
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect::new_from_x_y(
+            self.center.x - PICKUP_RADIUS,
+            self.center.y - PICKUP_RADIUS,
+            PICKUP_RADIUS * 2,
+            PICKUP_RADIUS * 2,
+        )
+    }
+}
+
+impl Obstacle for PowerUpPickup {
+    fn contact(&self, sweep: &Sweep) -> Option<Contact> {
+        if sweep.impact(&self.bounding_box()).is_some() {
+            Some(Contact::PowerUp(self.power_up))
+        } else {
+            None
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_circle(&self.center, PICKUP_RADIUS, self.power_up.color());
+        let text_position = Point {
+            x: self.center.x + TEXT_OFFSET.x,
+            y: self.center.y + TEXT_OFFSET.y,
+        };
+        if let Err(err) = renderer.draw_text(self.power_up.label(), &text_position) {
+            error!("Could not draw power up {:#?}", err);
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i32) {
+        self.center.x += x;
+    }
+
+    fn right(&self) -> i32 {
+        self.bounding_box().right()
+    }
+
+    fn consume(&mut self) {
+        self.collected = true;
+    }
+
+    fn consumed(&self) -> bool {
+        self.collected
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        vec![self.bounding_box()]
+    }
+
+    fn layer(&self) -> Layers {
+        PICKUP
+    }
+
+    fn name(&self) -> &'static str {
+        "power up"
+    }
+
+    // Which power up it gives is picked again, the same as a new one
+    fn respawn(&mut self, piece: &Piece) -> bool {
+        match piece {
+            Piece::PowerUp(center) => {
+                self.center = *center;
+                self.power_up = *POWER_UPS
+                    .choose(&mut thread_rng())
+                    .unwrap_or(&self.power_up);
+                self.collected = false;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// Moves a collectible at `position` a step closer to `target` when it's in range
+pub fn attract(position: &mut Point, target: &Point) {
+    let distance_x = target.x - position.x;
+    let distance_y = target.y - position.y;
+
+    if distance_x.abs() <= MAGNET_RADIUS && distance_y.abs() <= MAGNET_RADIUS {
+        position.x += distance_x.clamp(-MAGNET_PULL, MAGNET_PULL);
+        position.y += distance_y.clamp(-MAGNET_PULL, MAGNET_PULL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_ups_run_out() {
+        let mut power_ups = PowerUps::default();
+        power_ups.activate(PowerUp::SlowMotion);
+
+        (0..PowerUp::SlowMotion.duration() - 1).for_each(|_| power_ups.tick());
+        assert!(power_ups.is_active(PowerUp::SlowMotion));
+        assert_eq!(power_ups.seconds_left(PowerUp::SlowMotion), 1);
+
+        power_ups.tick();
+        assert!(!power_ups.is_active(PowerUp::SlowMotion));
+    }
+
+    #[test]
+    fn only_active_power_ups_are_listed() {
+        let mut power_ups = PowerUps::default();
+        power_ups.activate(PowerUp::Magnet);
+        power_ups.activate(PowerUp::Shield);
+
+        let active: Vec<PowerUp> = power_ups.active().collect();
+
+        assert_eq!(active, vec![PowerUp::Shield, PowerUp::Magnet]);
+    }
+
+    #[test]
+    fn the_magnet_only_pulls_nearby_things() {
+        let target = Point { x: 0, y: 0 };
+        let mut near = Point { x: 100, y: -3 };
+        let mut far = Point {
+            x: MAGNET_RADIUS + 1,
+            y: 0,
+        };
+
+        attract(&mut near, &target);
+        attract(&mut far, &target);
+
+        assert_eq!(
+            near,
+            Point {
+                x: 100 - MAGNET_PULL,
+                y: 0
+            }
+        );
+        assert_eq!(
+            far,
+            Point {
+                x: MAGNET_RADIUS + 1,
+                y: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use super::{
+    hitboxes::{hitbox_for, place, WHOLE_FRAME},
+    moving_platform_offset,
+    red_hat_boy_states::{
+        COYOTE_TICKS, FLOOR, GRAVITY, JUMP_SPEED, PLAYER_HEIGHT, RUNNING_SPEED, SLIDING_FRAMES,
+        SPRING_SPEED, STARTING_POINT, TERMINAL_VELOCITY,
+    },
+    EnemyKind, PlatformKind, HEIGHT,
+};
+use crate::{
+    engine::{Fixed, Normal, Point, Rect, Sweep},
+    segments::{Piece, Segment, SEGMENTS},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+    Jump,
+    Slide,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Pose {
+    Running,
+    Jumping,
+    Sliding(u8),
+}
+
+// A stripped down RedHatBoyContext. `x` is how far the world has scrolled,
+// which is the same as the boy moving right through a static course.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Body {
+    x: i32,
+    y: Fixed,
+    last_y: Fixed,
+    velocity_y: Fixed,
+    // Capped just past the coyote window so it doesn't grow the search space
+    airborne_ticks: u8,
+    pose: Pose,
+}
+
+impl Body {
+    fn start() -> Self {
+        Body {
+            x: 0,
+            y: FLOOR.into(),
+            last_y: FLOOR.into(),
+            velocity_y: Fixed::ZERO,
+            airborne_ticks: 0,
+            pose: Pose::Running,
+        }
+    }
+
+    // Jumps are always full height, so a course that only a short hop gets
+    // through is rejected. A buffered jump is the same as pressing on landing.
+    fn press(mut self, input: Input) -> Self {
+        if self.pose == Pose::Running {
+            match input {
+                Input::Jump if self.airborne_ticks > COYOTE_TICKS => {}
+                Input::Jump => {
+                    self.velocity_y = JUMP_SPEED;
+                    self.pose = Pose::Jumping;
+                }
+                Input::Slide => self.pose = Pose::Sliding(0),
+            }
+        }
+        self
+    }
+
+    // Mirrors RedHatBoyContext::update followed by the per state update
+    fn update(mut self) -> Self {
+        self.last_y = self.y;
+        if self.velocity_y < TERMINAL_VELOCITY {
+            self.velocity_y += GRAVITY;
+        }
+
+        self.y += self.velocity_y;
+        self.airborne_ticks = (self.airborne_ticks + 1).min(COYOTE_TICKS + 1);
+
+        self.pose = match self.pose {
+            Pose::Sliding(frame) if frame + 1 >= SLIDING_FRAMES => Pose::Running,
+            Pose::Sliding(frame) => Pose::Sliding(frame + 1),
+            pose => pose,
+        };
+
+        self.x += RUNNING_SPEED;
+        self
+    }
+
+    fn land_on(mut self, position: i32) -> Self {
+        self.y = (position - PLAYER_HEIGHT).into();
+        self.airborne_ticks = 0;
+        if self.pose == Pose::Jumping {
+            self.pose = Pose::Running;
+        }
+        self
+    }
+
+    // Landing on a springboard while running or sliding
+    fn spring(mut self) -> Self {
+        if self.pose != Pose::Jumping {
+            self.velocity_y = SPRING_SPEED;
+            self.pose = Pose::Jumping;
+        }
+        self
+    }
+
+    // The same as RedHatBoy::sweep
+    fn sweep(&self) -> Sweep {
+        let motion = Point {
+            x: RUNNING_SPEED,
+            y: self.y.round() - self.last_y.round(),
+        };
+        Sweep::ending_at(self.bounding_box(), motion)
+    }
+
+    // The same hitboxes as RedHatBoy::bounding_box
+    fn bounding_box(&self) -> Rect {
+        let animation = match self.pose {
+            Pose::Running => "Run",
+            Pose::Jumping => "Jump",
+            Pose::Sliding(_) => "Slide",
+        };
+
+        // Without a hitbox to mirror, the whole frame is the cautious guess
+        place(
+            hitbox_for(animation).unwrap_or(WHOLE_FRAME),
+            Point {
+                x: STARTING_POINT + self.x,
+                y: self.y.round(),
+            },
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Behaviour {
+    Still,
+    // A moving platform, placed with its left edge at x
+    Moving(i32),
+    Springboard,
+    Enemy(EnemyKind, Point),
+}
+
+struct Shape {
+    lethal: bool,
+    bounding_boxes: Vec<Rect>,
+    behaviour: Behaviour,
+}
+
+impl Shape {
+    // Where the shape is once the world has scrolled `scrolled` pixels, only
+    // moving platforms and enemies are anywhere but where they were put
+    fn bounding_boxes_at(&self, scrolled: i32) -> Cow<'_, [Rect]> {
+        match self.behaviour {
+            Behaviour::Moving(x) => {
+                let offset = moving_platform_offset(x - scrolled);
+                Cow::Owned(
+                    self.bounding_boxes
+                        .iter()
+                        .map(|bounding_box| {
+                            Rect::new_from_x_y(
+                                bounding_box.x(),
+                                bounding_box.y() + offset,
+                                bounding_box.width(),
+                                bounding_box.height(),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            Behaviour::Enemy(kind, origin) => {
+                let on_screen = Point {
+                    x: origin.x - scrolled,
+                    y: origin.y,
+                };
+                let moved = kind.bounding_box(on_screen);
+                Cow::Owned(vec![Rect::new_from_x_y(
+                    moved.x() + scrolled,
+                    moved.y(),
+                    moved.width(),
+                    moved.height(),
+                )])
+            }
+            Behaviour::Still | Behaviour::Springboard => Cow::Borrowed(&self.bounding_boxes),
+        }
+    }
+
+    // Mirrors Platform::update, how far it moved on the last tick
+    fn velocity_at(&self, scrolled: i32) -> i32 {
+        match self.behaviour {
+            Behaviour::Moving(x) => {
+                moving_platform_offset(x - scrolled)
+                    - moving_platform_offset(x - scrolled + RUNNING_SPEED)
+            }
+            _ => 0,
+        }
+    }
+}
+
+pub struct Course {
+    shapes: Vec<Shape>,
+}
+
+impl Course {
+    pub fn new(pieces: &[Piece], stone_width: i32, stone_height: i32) -> Self {
+        // Coins and other pickups can't change whether a course is survivable
+        let shapes = pieces
+            .iter()
+            .filter(|piece| piece.is_solid() || piece.is_lethal())
+            .map(|piece| {
+                let behaviour = match piece {
+                    Piece::FloatingPlatform(position, PlatformKind::Moving) => {
+                        Behaviour::Moving(position.x)
+                    }
+                    Piece::Springboard(_) => Behaviour::Springboard,
+                    Piece::Enemy(kind, origin) => Behaviour::Enemy(*kind, *origin),
+                    _ => Behaviour::Still,
+                };
+                // A crumbling platform may be gone by the time the boy gets to
+                // it, so a course has to be survivable without touching it
+                let crumbling =
+                    matches!(piece, Piece::FloatingPlatform(_, PlatformKind::Crumbling));
+                Shape {
+                    lethal: piece.is_lethal() || crumbling,
+                    bounding_boxes: piece.bounding_boxes(stone_width, stone_height),
+                    behaviour,
+                }
+            })
+            .collect();
+
+        Course { shapes }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.shapes
+            .iter()
+            .flat_map(|shape| shape.bounding_boxes.iter())
+            .map(Rect::right)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Mirrors Barrier and Platform contact and falling into a pit,
+    // None meaning knocked out
+    fn collide(&self, mut body: Body) -> Option<Body> {
+        for shape in &self.shapes {
+            let sweep = body.sweep().relative_to(Point {
+                x: 0,
+                y: shape.velocity_at(body.x),
+            });
+            let bounding_boxes = shape.bounding_boxes_at(body.x);
+            if let Some((box_to_land_on, impact)) = bounding_boxes
+                .iter()
+                .filter_map(|candidate| Some((candidate, sweep.impact(candidate)?)))
+                .min_by_key(|(_, impact)| impact.time)
+            {
+                if shape.lethal || impact.normal != Normal::Up {
+                    return None;
+                }
+                body = body.land_on(box_to_land_on.y());
+                if let Behaviour::Springboard = shape.behaviour {
+                    body = body.spring();
+                }
+            }
+        }
+
+        if body.y > HEIGHT.into() {
+            None
+        } else {
+            Some(body)
+        }
+    }
+}
+
+struct Node {
+    body: Body,
+    parent: Option<usize>,
+    input: Option<Input>,
+}
+
+#[derive(Debug)]
+pub struct ReachabilityReport {
+    pub survivable: bool,
+    // Left edge of the boy's hitbox, in course coordinates, at the furthest
+    // point any input sequence reached
+    pub furthest: i32,
+    // The inputs of the run that got furthest, with where they were pressed
+    pub inputs: Vec<(i32, Input)>,
+}
+
+// Breadth first search over every tick at which the boy could jump, slide or
+// do nothing, using the same physics constants and update order as the game.
+pub fn validate(course: &Course) -> ReachabilityReport {
+    let finish = course.right();
+    let mut nodes = vec![Node {
+        body: Body::start(),
+        parent: None,
+        input: None,
+    }];
+    let mut frontier = vec![0];
+
+    loop {
+        if let Some(&winner) = frontier
+            .iter()
+            .find(|&&index| nodes[index].body.bounding_box().left() > finish)
+        {
+            return report(&nodes, winner, true);
+        }
+
+        let mut visited = HashSet::new();
+        let mut next_frontier = vec![];
+        for &index in &frontier {
+            let body = nodes[index].body;
+            let inputs: &[Option<Input>] = if body.pose == Pose::Running {
+                &[None, Some(Input::Jump), Some(Input::Slide)]
+            } else {
+                &[None]
+            };
+
+            for &input in inputs {
+                let pressed = input.map_or(body, |input| body.press(input));
+                if let Some(next) = course.collide(pressed.update()) {
+                    if visited.insert(next) {
+                        nodes.push(Node {
+                            body: next,
+                            parent: Some(index),
+                            input,
+                        });
+                        next_frontier.push(nodes.len() - 1);
+                    }
+                }
+            }
+        }
+
+        if next_frontier.is_empty() {
+            return report(&nodes, frontier[0], false);
+        }
+        frontier = next_frontier;
+    }
+}
+
+fn report(nodes: &[Node], last: usize, survivable: bool) -> ReachabilityReport {
+    let mut inputs = vec![];
+    let mut index = last;
+    while let Some(parent) = nodes[index].parent {
+        if let Some(input) = nodes[index].input {
+            inputs.push((nodes[parent].body.bounding_box().left(), input));
+        }
+        index = parent;
+    }
+    inputs.reverse();
+
+    ReachabilityReport {
+        survivable,
+        furthest: nodes[last].body.bounding_box().left(),
+        inputs,
+    }
+}
+
+#[derive(Debug)]
+pub struct UnsurvivableSegment {
+    pub previous: Option<Segment>,
+    pub next: Segment,
+    pub report: ReachabilityReport,
+}
+
+// Caches verdicts for "segment `next` directly after segment `previous`",
+// where no previous segment means open ground. Open ground itself is never
+// validated, running along flat ground is always survivable.
+pub struct Reachability {
+    stone_width: i32,
+    stone_height: i32,
+    verdicts: HashMap<(Option<Segment>, Segment), bool>,
+}
+
+impl Reachability {
+    pub fn new(stone_width: i32, stone_height: i32) -> Self {
+        Reachability {
+            stone_width,
+            stone_height,
+            verdicts: HashMap::new(),
+        }
+    }
+
+    pub fn course(&self, previous: Option<Segment>, next: Segment) -> Course {
+        let mut pieces = vec![];
+        let mut offset_x = 0;
+
+        if let Some(previous) = previous {
+            pieces = previous.layout(0);
+            let course = Course::new(&pieces, self.stone_width, self.stone_height);
+            offset_x = course.right();
+        }
+
+        pieces.append(&mut next.layout(offset_x));
+        Course::new(&pieces, self.stone_width, self.stone_height)
+    }
+
+    pub fn is_survivable(&mut self, previous: Option<Segment>, next: Segment) -> bool {
+        if let Some(&verdict) = self.verdicts.get(&(previous, next)) {
+            return verdict;
+        }
+
+        let verdict = validate(&self.course(previous, next)).survivable;
+        self.verdicts.insert((previous, next), verdict);
+        verdict
+    }
+
+    pub fn unsurvivable(&self) -> Vec<UnsurvivableSegment> {
+        let previous_segments = std::iter::once(None).chain(SEGMENTS.iter().copied().map(Some));
+
+        previous_segments
+            .flat_map(|previous| SEGMENTS.iter().map(move |&next| (previous, next)))
+            .map(|(previous, next)| UnsurvivableSegment {
+                previous,
+                next,
+                report: validate(&self.course(previous, next)),
+            })
+            .filter(|unsurvivable| !unsurvivable.report.survivable)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GROUND_TOP;
+
+    const STONE_WIDTH: i32 = 90;
+    const STONE_HEIGHT: i32 = 54;
+    const GROUND: Piece = Piece::Ground(
+        Point {
+            x: 0,
+            y: GROUND_TOP,
+        },
+        &["2.png"; 8],
+    );
+
+    #[test]
+    fn every_shipped_segment_pair_is_survivable() {
+        let reachability = Reachability::new(STONE_WIDTH, STONE_HEIGHT);
+
+        let unsurvivable = reachability.unsurvivable();
+
+        assert!(unsurvivable.is_empty(), "{:#?}", unsurvivable);
+    }
+
+    #[test]
+    fn open_ground_needs_no_input() {
+        let course = Course::new(&Segment::OpenGround.layout(0), STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(report.survivable);
+        assert!(report.inputs.is_empty());
+    }
+
+    #[test]
+    fn a_stone_needs_a_jump_to_clear() {
+        let stone = Piece::Stone(Point {
+            x: 300,
+            y: GROUND_TOP - STONE_HEIGHT,
+        });
+        let course = Course::new(&[GROUND, stone], STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(report.survivable);
+        assert_eq!(report.inputs.len(), 1);
+        assert_eq!(report.inputs[0].1, Input::Jump);
+    }
+
+    #[test]
+    fn a_stone_longer_than_a_jump_is_unsurvivable() {
+        let stone = Piece::Stone(Point {
+            x: 300,
+            y: GROUND_TOP - STONE_HEIGHT,
+        });
+        let course = Course::new(&[GROUND, stone], 400, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(!report.survivable);
+        assert!(report.furthest < 300 + 400);
+    }
+
+    #[test]
+    fn an_overhang_needs_a_slide_to_clear() {
+        let course = Course::new(&Segment::Overhang.layout(0), STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(report.survivable);
+        assert!(report
+            .inputs
+            .iter()
+            .any(|&(_, input)| input == Input::Slide));
+        assert!(report.inputs.iter().all(|&(_, input)| input != Input::Jump));
+    }
+
+    #[test]
+    fn a_jump_only_counts_just_after_leaving_the_ground() {
+        let just_off_the_edge = Body::start().update();
+        let falling = (0..=COYOTE_TICKS).fold(Body::start(), |body, _| body.update());
+
+        assert!(just_off_the_edge.press(Input::Jump).pose == Pose::Jumping);
+        assert!(falling.press(Input::Jump).pose == Pose::Running);
+    }
+
+    #[test]
+    fn a_walker_needs_a_jump_to_clear() {
+        let course = Course::new(&Segment::Walker.layout(0), STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(report.survivable);
+        assert_eq!(report.inputs.len(), 1);
+        assert_eq!(report.inputs[0].1, Input::Jump);
+    }
+
+    #[test]
+    fn a_pit_needs_a_jump_to_clear() {
+        let course = Course::new(&Segment::Pit.layout(0), STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(report.survivable);
+        assert_eq!(report.inputs.len(), 1);
+        assert_eq!(report.inputs[0].1, Input::Jump);
+    }
+
+    #[test]
+    fn a_wide_pit_is_crossed_on_the_moving_platform() {
+        let layout = Segment::MovingPlatform.layout(0);
+        let without_platform: Vec<Piece> = layout
+            .iter()
+            .copied()
+            .filter(|piece| !matches!(piece, Piece::FloatingPlatform(_, _)))
+            .collect();
+
+        let with = validate(&Course::new(&layout, STONE_WIDTH, STONE_HEIGHT));
+        let without = validate(&Course::new(&without_platform, STONE_WIDTH, STONE_HEIGHT));
+
+        assert!(with.survivable);
+        assert!(!without.survivable);
+    }
+
+    #[test]
+    fn a_pit_wider_than_a_jump_is_unsurvivable() {
+        let far_ground = Piece::Ground(
+            Point {
+                x: 1024 + 400,
+                y: GROUND_TOP,
+            },
+            &["2.png"; 2],
+        );
+        let course = Course::new(&[GROUND, far_ground], STONE_WIDTH, STONE_HEIGHT);
+
+        let report = validate(&course);
+
+        assert!(!report.survivable);
+    }
+}
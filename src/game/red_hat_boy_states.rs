@@ -1,22 +1,44 @@
-use crate::engine::{Audio, Point, Sound};
+use std::rc::Rc;
 
-const FLOOR: i16 = 479;
-const PLAYER_HEIGHT: i16 = super::HEIGHT - FLOOR;
-const STARTING_POINT: i16 = -20;
+use crate::engine::{Audio, Fixed, Point, Sound, Vector};
+
+use super::power_ups::SLOW_MOTION_FACTOR;
+
+pub const PLAYER_HEIGHT: i32 = 121;
+pub const FLOOR: i32 = super::GROUND_TOP - PLAYER_HEIGHT;
+pub const STARTING_POINT: i32 = -20;
 const IDLE_FRAME_NAME: &str = "Idle";
 const RUNNING_FRAME_NAME: &str = "Run";
 const SLIDING_FRAME_NAME: &str = "Slide";
 const JUMPING_FRAME_NAME: &str = "Jump";
 const FALLING_FRAME_NAME: &str = "Dead";
+const HURT_FRAME_NAME: &str = "Hurt";
 const IDLE_FRAMES: u8 = 29;
 const RUNNING_FRAMES: u8 = 23;
-const SLIDING_FRAMES: u8 = 14;
+pub const SLIDING_FRAMES: u8 = 39;
 const JUMPING_FRAMES: u8 = 35;
 const FALLING_FRAMES: u8 = 29;
-const RUNNING_SPEED: i16 = 4;
-const JUMP_SPEED: i16 = -25;
-const GRAVITY: i16 = 1;
-const TERMINAL_VELOCITY: i16 = 20;
+pub const TICKS_PER_IMAGE: u8 = 3;
+// The slide has only five images but has to last long enough to get under an overhang
+pub const SLIDING_TICKS_PER_IMAGE: u8 = 8;
+pub const HURT_FRAMES: u8 = 23;
+pub const RUNNING_SPEED: i32 = 4;
+pub const JUMP_SPEED: Fixed = Fixed::from_int(-25);
+pub const GRAVITY: Fixed = Fixed::from_int(1);
+pub const TERMINAL_VELOCITY: Fixed = Fixed::from_int(20);
+// Letting go of jump early caps the upward speed, cutting the jump short
+pub const JUMP_RELEASE_SPEED: Fixed = Fixed::from_int(-10);
+// How long after running off an edge a jump still counts
+pub const COYOTE_TICKS: u8 = 6;
+// How long a jump pressed in the air is remembered, to go off on landing
+pub const JUMP_BUFFER_TICKS: u8 = 8;
+// Holding down keeps a slide going until the stamina runs out, and running
+// earns it back a tick at a time. A slide always lasts at least SLIDING_FRAMES.
+pub const SLIDE_STAMINA: u8 = 120;
+// Pressing down in the air drops the boy faster than he could ever fall
+pub const FAST_FALL_SPEED: Fixed = Fixed::from_int(30);
+// A springboard throws the boy higher than he could jump
+pub const SPRING_SPEED: Fixed = Fixed::from_int(-28);
 
 #[derive(Clone)]
 pub struct RedHatBoyState<S> {
@@ -35,20 +57,34 @@ impl RedHatBoyState<Idle> {
         IDLE_FRAME_NAME
     }
 
+    // Idling happens on solid ground before any obstacle is checked, so no gravity
     pub fn update(mut self) -> Self {
-        self.context = self.context.update(IDLE_FRAMES);
+        self.context = self.context.animate(IDLE_FRAMES);
         self
     }
 
-    pub fn new(audio: Audio, jump_sound: Sound) -> Self {
+    pub fn new(audio: Rc<Audio>, jump_sound: Rc<Sound>) -> Self {
         RedHatBoyState {
             context: RedHatBoyContext {
                 frame: 0,
-                position: Point {
+                position: Vector::from(Point {
                     x: STARTING_POINT,
                     y: FLOOR,
-                },
-                velocity: Point { x: 0, y: 0 },
+                }),
+                velocity: Vector::default(),
+                last_position: Vector::from(Point {
+                    x: STARTING_POINT,
+                    y: FLOOR,
+                }),
+                travelled: Fixed::ZERO,
+                stride: 0,
+                slow_motion: false,
+                air_jumped: false,
+                airborne_ticks: 0,
+                jump_buffer: 0,
+                slide_held: false,
+                slide_ticks: 0,
+                slide_stamina: SLIDE_STAMINA,
                 audio,
                 jump_sound,
             },
@@ -70,58 +106,136 @@ impl RedHatBoyState<Running> {
     }
 
     pub fn update(mut self) -> Self {
-        self.context = self.context.update(RUNNING_FRAMES);
+        self.context = self.context.update(RUNNING_FRAMES).recover_stamina();
         self
     }
 
-    pub fn slide(self) -> RedHatBoyState<Sliding> {
-        RedHatBoyState {
-            context: self.context.reset_frame(),
+    // Too tired for a full slide means no slide at all
+    pub fn slide(self) -> SlideEndState {
+        if self.context.slide_stamina < SLIDING_FRAMES {
+            return SlideEndState::Running(self);
+        }
+
+        SlideEndState::Sliding(RedHatBoyState {
+            context: self.context.reset_frame().start_slide(),
             _state: Sliding {},
+        })
+    }
+
+    // Running covers walking off an edge too, so only jump while on the
+    // ground or just off it, and otherwise remember the press for landing
+    pub fn jump(self) -> JumpEndState {
+        if self.context.airborne_ticks > COYOTE_TICKS {
+            return JumpEndState::Running(RedHatBoyState {
+                context: self.context.buffer_jump(),
+                _state: Running,
+            });
         }
+
+        JumpEndState::Jumping(self.take_off().play_jump_sound())
     }
 
-    pub fn jump(self) -> RedHatBoyState<Jumping> {
+    // Everything about a jump but the sound, which needs a browser to play
+    pub fn take_off(self) -> RedHatBoyState<Jumping> {
         RedHatBoyState {
             context: self
                 .context
                 .reset_frame()
                 .set_vertical_velocity(JUMP_SPEED)
+                .set_air_jumped(false)
+                .clear_jump_buffer(),
+            _state: Jumping {},
+        }
+    }
+
+    pub fn spring(self) -> RedHatBoyState<Jumping> {
+        let jumping = self.take_off();
+        RedHatBoyState {
+            context: jumping
+                .context
+                .set_vertical_velocity(SPRING_SPEED)
                 .play_jump_sound(),
             _state: Jumping {},
         }
     }
 
+    fn jump_if_buffered(self) -> JumpEndState {
+        if self.context.jump_buffer > 0 {
+            self.jump()
+        } else {
+            JumpEndState::Running(self)
+        }
+    }
+
     pub fn knock_out(self) -> RedHatBoyState<Falling> {
         RedHatBoyState {
-            context: self.context.reset_frame().set_vertical_velocity(0).stop(),
+            context: self
+                .context
+                .reset_frame()
+                .set_vertical_velocity(Fixed::ZERO)
+                .stop(),
             _state: Falling {},
         }
     }
 
-    pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+    pub fn hurt(self) -> RedHatBoyState<Hurt> {
+        RedHatBoyState {
+            context: self.context.reset_frame(),
+            _state: Hurt {},
+        }
+    }
+
+    pub fn land_on(self, position: i32) -> JumpEndState {
         RedHatBoyState {
             context: self.context.set_on(position),
             _state: Running {},
         }
+        .jump_if_buffered()
     }
 }
 
+pub enum JumpEndState {
+    Running(RedHatBoyState<Running>),
+    Jumping(RedHatBoyState<Jumping>),
+}
+
+pub enum SlideEndState {
+    Running(RedHatBoyState<Running>),
+    Sliding(RedHatBoyState<Sliding>),
+}
+
 impl RedHatBoyState<Sliding> {
     pub fn frame_name(&self) -> &str {
         SLIDING_FRAME_NAME
     }
 
+    // The animation loops for as long as the slide is held
     pub fn update(mut self) -> SlidingEndState {
-        self.context = self.context.update(SLIDING_FRAMES);
+        self.context = self.context.update(SLIDING_FRAMES).use_stamina();
 
-        if self.context.frame >= SLIDING_FRAMES {
+        let exhausted = self.context.slide_stamina == 0;
+        let released = !self.context.slide_held && self.context.slide_ticks >= SLIDING_FRAMES;
+        if exhausted || released {
             SlidingEndState::Complete(self.stand())
         } else {
             SlidingEndState::Sliding(self)
         }
     }
 
+    pub fn hold(mut self) -> Self {
+        self.context.slide_held = true;
+        self
+    }
+
+    pub fn release(mut self) -> Self {
+        self.context.slide_held = false;
+        self
+    }
+
+    pub fn spring(self) -> RedHatBoyState<Jumping> {
+        self.stand().spring()
+    }
+
     fn stand(&self) -> RedHatBoyState<Running> {
         RedHatBoyState {
             context: self.context.clone().reset_frame(),
@@ -131,12 +245,23 @@ impl RedHatBoyState<Sliding> {
 
     pub fn knock_out(self) -> RedHatBoyState<Falling> {
         RedHatBoyState {
-            context: self.context.set_vertical_velocity(0).reset_frame().stop(),
+            context: self
+                .context
+                .set_vertical_velocity(Fixed::ZERO)
+                .reset_frame()
+                .stop(),
             _state: Falling {},
         }
     }
 
-    pub fn land_on(self, position: i16) -> RedHatBoyState<Sliding> {
+    pub fn hurt(self) -> RedHatBoyState<Hurt> {
+        RedHatBoyState {
+            context: self.context.reset_frame(),
+            _state: Hurt {},
+        }
+    }
+
+    pub fn land_on(self, position: i32) -> RedHatBoyState<Sliding> {
         RedHatBoyState {
             context: self.context.set_on(position),
             _state: Sliding {},
@@ -154,34 +279,125 @@ impl RedHatBoyState<Jumping> {
         JUMPING_FRAME_NAME
     }
 
-    pub fn update(mut self) -> JumpingEndState {
+    pub fn update(mut self) -> Self {
         self.context = self.context.update(JUMPING_FRAMES);
+        self
+    }
 
-        if self.context.position.y >= FLOOR {
-            JumpingEndState::Landing(self.land_on(super::HEIGHT))
-        } else {
-            JumpingEndState::Jumping(self)
+    pub fn fast_fall(mut self) -> Self {
+        if self.context.velocity.y < FAST_FALL_SPEED {
+            self.context = self.context.set_vertical_velocity(FAST_FALL_SPEED);
         }
+        self
+    }
+
+    fn play_jump_sound(mut self) -> Self {
+        self.context = self.context.play_jump_sound();
+        self
     }
 
-    pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+    pub fn buffer_jump(mut self) -> Self {
+        self.context = self.context.buffer_jump();
+        self
+    }
+
+    pub fn release_jump(mut self) -> Self {
+        if self.context.velocity.y < JUMP_RELEASE_SPEED {
+            self.context = self.context.set_vertical_velocity(JUMP_RELEASE_SPEED);
+        }
+        self
+    }
+
+    // Only one extra jump is allowed before landing
+    pub fn double_jump(self) -> RedHatBoyState<Jumping> {
+        if self.context.air_jumped {
+            return self;
+        }
+
+        RedHatBoyState {
+            context: self
+                .context
+                .reset_frame()
+                .set_vertical_velocity(JUMP_SPEED)
+                .set_air_jumped(true),
+            _state: Jumping {},
+        }
+        .play_jump_sound()
+    }
+
+    pub fn land_on(self, position: i32) -> JumpEndState {
         RedHatBoyState {
             context: self.context.set_on(position).reset_frame(),
             _state: Running,
         }
+        .jump_if_buffered()
     }
 
     pub fn knock_out(self) -> RedHatBoyState<Falling> {
         RedHatBoyState {
-            context: self.context.set_vertical_velocity(0).reset_frame().stop(),
+            context: self
+                .context
+                .set_vertical_velocity(Fixed::ZERO)
+                .reset_frame()
+                .stop(),
             _state: Falling {},
         }
     }
+
+    pub fn hurt(self) -> RedHatBoyState<Hurt> {
+        RedHatBoyState {
+            context: self.context.reset_frame(),
+            _state: Hurt {},
+        }
+    }
 }
 
-pub enum JumpingEndState {
-    Landing(RedHatBoyState<Running>),
-    Jumping(RedHatBoyState<Jumping>),
+// Getting hit with lives to spare, the boy keeps running and can't jump or
+// slide until the animation is over
+impl RedHatBoyState<Hurt> {
+    pub fn frame_name(&self) -> &str {
+        HURT_FRAME_NAME
+    }
+
+    pub fn update(mut self) -> HurtEndState {
+        self.context = self.context.update(HURT_FRAMES);
+
+        if self.context.frame >= HURT_FRAMES {
+            HurtEndState::Complete(self.recover())
+        } else {
+            HurtEndState::Hurt(self)
+        }
+    }
+
+    fn recover(&self) -> RedHatBoyState<Running> {
+        RedHatBoyState {
+            context: self.context.clone().reset_frame(),
+            _state: Running,
+        }
+    }
+
+    pub fn land_on(self, position: i32) -> RedHatBoyState<Hurt> {
+        RedHatBoyState {
+            context: self.context.set_on(position),
+            _state: Hurt {},
+        }
+    }
+
+    pub fn knock_out(self) -> RedHatBoyState<Falling> {
+        RedHatBoyState {
+            context: self
+                .context
+                .set_vertical_velocity(Fixed::ZERO)
+                .reset_frame()
+                .stop(),
+            _state: Falling {},
+        }
+    }
+}
+
+pub enum HurtEndState {
+    Complete(RedHatBoyState<Running>),
+    Hurt(RedHatBoyState<Hurt>),
 }
 
 impl RedHatBoyState<Falling> {
@@ -199,6 +415,13 @@ impl RedHatBoyState<Falling> {
         }
     }
 
+    pub fn land_on(self, position: i32) -> RedHatBoyState<Falling> {
+        RedHatBoyState {
+            context: self.context.set_on(position),
+            _state: Falling {},
+        }
+    }
+
     fn die(&self) -> RedHatBoyState<Dead> {
         RedHatBoyState {
             context: self.context.clone(),
@@ -221,32 +444,63 @@ impl RedHatBoyState<Dead> {
 #[derive(Clone)]
 pub struct RedHatBoyContext {
     pub frame: u8,
-    pub position: Point,
-    pub velocity: Point,
-    pub audio: Audio,
-    pub jump_sound: Sound,
+    pub position: Vector,
+    pub velocity: Vector,
+    // Where the last update started, so collisions can follow the whole move
+    pub last_position: Vector,
+    // Obstacles scroll by whole pixels, the fraction left over carries to the next tick
+    pub travelled: Fixed,
+    pub stride: i32,
+    pub slow_motion: bool,
+    pub air_jumped: bool,
+    pub airborne_ticks: u8,
+    pub jump_buffer: u8,
+    pub slide_held: bool,
+    pub slide_ticks: u8,
+    pub slide_stamina: u8,
+    // Shared so that cloning the context doesn't reach into the browser
+    pub audio: Rc<Audio>,
+    pub jump_sound: Rc<Sound>,
 }
 
 impl RedHatBoyContext {
     fn update(mut self, frame_count: u8) -> Self {
+        self.last_position = self.position;
+        // Slow motion slows time itself, so a jump keeps its shape and covers
+        // the same ground, only taking longer to do it
+        let slow_motion = self.slow_motion;
+        let slowed = |value: Fixed| {
+            if slow_motion {
+                value / SLOW_MOTION_FACTOR
+            } else {
+                value
+            }
+        };
         if self.velocity.y < TERMINAL_VELOCITY {
-            self.velocity.y += GRAVITY;
+            self.velocity.y += slowed(GRAVITY);
         }
 
-        if self.frame < frame_count {
-            self.frame += 1;
-        } else {
-            self.frame = 0;
-        }
+        self = self.animate(frame_count);
+        self.airborne_ticks = self.airborne_ticks.saturating_add(1);
+        self.jump_buffer = self.jump_buffer.saturating_sub(1);
 
         // Now it's the background that is going to move left instead of RHB moving right
         // self.position.x += self.velocity.x;
-        self.position.y += self.velocity.y;
+        self.travelled += slowed(self.velocity.x);
+        self.stride = self.travelled.floor();
+        self.travelled -= self.stride.into();
+        // There is no floor to clamp to either, the ground is an obstacle to land on
+        self.position.y += slowed(self.velocity.y);
 
-        if self.position.y > FLOOR {
-            self.position.y = FLOOR;
-        }
+        self
+    }
 
+    fn animate(mut self, frame_count: u8) -> Self {
+        if self.frame < frame_count {
+            self.frame += 1;
+        } else {
+            self.frame = 0;
+        }
         self
     }
 
@@ -256,23 +510,58 @@ impl RedHatBoyContext {
     }
 
     pub fn run_right(mut self) -> Self {
-        self.velocity.x += RUNNING_SPEED;
+        self.velocity.x += RUNNING_SPEED.into();
         self
     }
 
-    pub fn set_vertical_velocity(mut self, y: i16) -> Self {
+    pub fn set_vertical_velocity(mut self, y: Fixed) -> Self {
         self.velocity.y = y;
         self
     }
 
+    fn start_slide(mut self) -> Self {
+        self.slide_held = true;
+        self.slide_ticks = 0;
+        self
+    }
+
+    fn use_stamina(mut self) -> Self {
+        self.slide_ticks = self.slide_ticks.saturating_add(1);
+        self.slide_stamina = self.slide_stamina.saturating_sub(1);
+        self
+    }
+
+    fn recover_stamina(mut self) -> Self {
+        self.slide_stamina = (self.slide_stamina + 1).min(SLIDE_STAMINA);
+        self
+    }
+
+    fn buffer_jump(mut self) -> Self {
+        self.jump_buffer = JUMP_BUFFER_TICKS;
+        self
+    }
+
+    fn clear_jump_buffer(mut self) -> Self {
+        self.jump_buffer = 0;
+        self
+    }
+
+    fn set_air_jumped(mut self, air_jumped: bool) -> Self {
+        self.air_jumped = air_jumped;
+        self
+    }
+
     pub fn stop(mut self) -> Self {
-        self.velocity.x = 0;
+        self.velocity.x = Fixed::ZERO;
+        self.travelled = Fixed::ZERO;
+        self.stride = 0;
         self
     }
 
-    pub fn set_on(mut self, position: i16) -> Self {
+    pub fn set_on(mut self, position: i32) -> Self {
         let position = position - PLAYER_HEIGHT;
-        self.position.y = position;
+        self.position.y = position.into();
+        self.airborne_ticks = 0;
         self
     }
 
@@ -296,6 +585,9 @@ pub struct Sliding;
 #[derive(Copy, Clone)]
 pub struct Jumping;
 
+#[derive(Copy, Clone)]
+pub struct Hurt;
+
 #[derive(Copy, Clone)]
 pub struct Falling;
 
@@ -0,0 +1,82 @@
+// Roughly the boy's height
+const PIXELS_PER_METRE: u32 = 60;
+
+// What happened on one run, for the game over panel
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunStats {
+    // In pixels
+    pub distance: u32,
+    // Hazards the boy got past, whether or not they hurt him on the way
+    pub obstacles_cleared: u32,
+    pub killed_by: Option<&'static str>,
+}
+
+impl RunStats {
+    // A run long enough to fill a u32 stops counting rather than wrapping
+    pub fn add_distance(&mut self, pixels: i32) {
+        self.distance = self.distance.saturating_add(pixels.max(0) as u32);
+    }
+
+    // Only the first thing to knock him out counts, not what he lands on after
+    pub fn knocked_out_by(&mut self, cause: &'static str) {
+        if self.killed_by.is_none() {
+            self.killed_by = Some(cause);
+        }
+    }
+
+    pub fn lines(&self, score: u32, best: u32) -> Vec<String> {
+        vec![
+            format!("Distance {}m", self.distance / PIXELS_PER_METRE),
+            format!("Score {}", score),
+            format!("Best {}", best),
+            format!("Obstacles cleared {}", self.obstacles_cleared),
+            format!("Killed by {}", self.killed_by.unwrap_or("nothing")),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_knock_out_is_what_killed_him() {
+        let mut stats = RunStats::default();
+
+        stats.knocked_out_by("stone");
+        stats.knocked_out_by("falling");
+
+        assert_eq!(stats.killed_by, Some("stone"));
+    }
+
+    #[test]
+    fn a_very_long_run_tops_out_instead_of_overflowing() {
+        let mut stats = RunStats {
+            distance: u32::MAX - 10,
+            ..RunStats::default()
+        };
+
+        (0..1000).for_each(|_| stats.add_distance(5));
+
+        assert_eq!(stats.distance, u32::MAX);
+        assert_eq!(
+            stats.lines(0, 0)[0],
+            format!("Distance {}m", u32::MAX / PIXELS_PER_METRE)
+        );
+    }
+
+    #[test]
+    fn distance_is_shown_in_metres() {
+        let stats = RunStats {
+            distance: PIXELS_PER_METRE * 12 + 5,
+            obstacles_cleared: 3,
+            killed_by: Some("walker"),
+        };
+
+        let lines = stats.lines(250, 400);
+
+        assert_eq!(lines[0], "Distance 12m");
+        assert_eq!(lines[2], "Best 400");
+        assert_eq!(lines[4], "Killed by walker");
+    }
+}
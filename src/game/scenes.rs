@@ -0,0 +1,401 @@
+use crate::engine::{
+    scene::{Scene, Transition},
+    ui::{Ui, UiEvent, Widget},
+    KeyState, Point, Rect, Renderer,
+};
+
+use super::{autopilot, Controls, Walk, DEBUG_OVERLAY_KEY, HEIGHT, STARTING_LIVES, WIDTH};
+
+const PAUSE_KEYS: [&str; 2] = ["Escape", "KeyP"];
+const RESTART_KEY: &str = "KeyR";
+// Half a second between the boy coming to rest and the game over panel
+const GAME_OVER_DELAY: u8 = 30;
+const PANEL_WIDTH: i32 = 440;
+const PANEL_CENTER: Point = Point {
+    x: WIDTH / 2,
+    y: HEIGHT / 2,
+};
+// Low enough to leave the logo room
+const TITLE_PANEL_CENTER: Point = Point {
+    x: WIDTH / 2,
+    y: HEIGHT / 2 + 60,
+};
+// Darkens the game behind a panel
+const SHADE_COLOR: &str = "rgba(0, 0, 0, 0.5)";
+const LOGO: &str = "Walk the Dog";
+const LOGO_FONT: &str = "64px 'Ken Future'";
+const LOGO_COLOR: &str = "#FFFFFF";
+const LOGO_POSITION: Point = Point { x: 390, y: 120 };
+const CREDITS: [&str; 3] = [
+    "Based on Game Development with",
+    "Rust and WebAssembly by Eric Smith",
+    "Ken Future font by Kenney",
+];
+
+fn pause_pressed(keystate: &KeyState) -> bool {
+    PAUSE_KEYS.iter().any(|key| keystate.was_just_pressed(key))
+}
+
+fn panel<T: Copy>(widgets: Vec<Widget<T>>) -> Ui<T> {
+    Ui::new(widgets, PANEL_CENTER, PANEL_WIDTH)
+}
+
+fn draw_panel<T: Copy>(walk: &Walk, renderer: &Renderer, ui: &Ui<T>) {
+    renderer.fill_rect(&Rect::new_from_x_y(0, 0, WIDTH, HEIGHT), SHADE_COLOR);
+    ui.draw(renderer, &walk.skin);
+}
+
+#[derive(Clone, Copy)]
+enum TitleItem {
+    Play,
+    Settings,
+    HighScores,
+    Credits,
+}
+
+// The main menu, over a walk the game plays by itself
+pub struct Title {
+    ui: Ui<TitleItem>,
+}
+
+impl Default for Title {
+    fn default() -> Self {
+        Title {
+            ui: Ui::new(
+                vec![
+                    Widget::button(TitleItem::Play, "Play"),
+                    Widget::button(TitleItem::Settings, "Settings"),
+                    Widget::button(TitleItem::HighScores, "High scores"),
+                    Widget::button(TitleItem::Credits, "Credits"),
+                ],
+                TITLE_PANEL_CENTER,
+                PANEL_WIDTH,
+            ),
+        }
+    }
+}
+
+impl Title {
+    fn start_attract(walk: &mut Walk) {
+        walk.reset();
+        walk.boy.run_right();
+    }
+}
+
+impl Scene<Walk> for Title {
+    fn update(&mut self, walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        if walk.is_dead() {
+            Title::start_attract(walk);
+        }
+        walk.step(autopilot::controls(walk));
+
+        match self.ui.update(keystate) {
+            Some(UiEvent::Pressed(TitleItem::Play)) => {
+                walk.reset();
+                walk.boy.run_right();
+                Transition::Clear(Box::new(Walking))
+            }
+            Some(UiEvent::Pressed(TitleItem::Settings)) => {
+                Transition::Push(Box::new(Settings::new(walk)))
+            }
+            Some(UiEvent::Pressed(TitleItem::HighScores)) => {
+                Transition::Push(Box::new(Notice::high_scores(walk.high_scores.scores())))
+            }
+            Some(UiEvent::Pressed(TitleItem::Credits)) => {
+                Transition::Push(Box::new(Notice::credits()))
+            }
+            _ => Transition::Stay,
+        }
+    }
+
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        walk.draw(renderer);
+        draw_panel(walk, renderer, &self.ui);
+        if let Err(err) = renderer.draw_styled_text(LOGO, &LOGO_POSITION, LOGO_FONT, LOGO_COLOR) {
+            error!("Could not draw the logo {:#?}", err);
+        }
+    }
+
+    fn enter(&mut self, walk: &mut Walk) {
+        Title::start_attract(walk);
+    }
+}
+
+// A panel to read, closed with its Back button or Escape
+pub struct Notice {
+    ui: Ui<()>,
+}
+
+impl Notice {
+    fn new(title: &str, lines: &[String]) -> Self {
+        let mut widgets = vec![Widget::label(title)];
+        widgets.extend(lines.iter().map(|line| Widget::label(line)));
+        widgets.push(Widget::button((), "Back"));
+        Notice { ui: panel(widgets) }
+    }
+
+    fn high_scores(scores: &[u32]) -> Self {
+        let lines: Vec<String> = if scores.is_empty() {
+            vec!["No scores yet".into()]
+        } else {
+            scores
+                .iter()
+                .enumerate()
+                .map(|(place, score)| format!("{}. {}", place + 1, score))
+                .collect()
+        };
+        Notice::new("High scores", &lines)
+    }
+
+    fn credits() -> Self {
+        let lines: Vec<String> = CREDITS.iter().map(|&line| line.into()).collect();
+        Notice::new("Credits", &lines)
+    }
+}
+
+impl Scene<Walk> for Notice {
+    fn update(&mut self, _walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        if keystate.was_just_pressed("Escape") {
+            return Transition::Pop;
+        }
+        match self.ui.update(keystate) {
+            Some(UiEvent::Pressed(())) => Transition::Pop,
+            _ => Transition::Stay,
+        }
+    }
+
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        walk.draw(renderer);
+        draw_panel(walk, renderer, &self.ui);
+    }
+}
+
+pub struct Walking;
+
+impl Scene<Walk> for Walking {
+    fn update(&mut self, walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        if keystate.was_just_pressed(DEBUG_OVERLAY_KEY) {
+            walk.debug.toggle();
+        }
+        if pause_pressed(keystate) {
+            return Transition::Push(Box::new(Paused::default()));
+        }
+
+        walk.step(Controls::from_keys(keystate));
+
+        if walk.is_dead() {
+            Transition::Replace(Box::new(GameOver::default()))
+        } else {
+            Transition::Stay
+        }
+    }
+
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        walk.draw(renderer);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum GameOverItem {
+    NewGame,
+    MainMenu,
+}
+
+// The run is over. The panel waits a moment so the boy is seen to land,
+// and a key still held from the run can't start the next one.
+#[derive(Default)]
+pub struct GameOver {
+    ui: Option<Ui<GameOverItem>>,
+    ticks: u8,
+}
+
+impl GameOver {
+    fn panel(walk: &Walk) -> Ui<GameOverItem> {
+        let best = walk.high_scores.scores().first().copied().unwrap_or(0);
+        let mut widgets = vec![Widget::label("Game over")];
+        widgets.extend(
+            walk.stats
+                .lines(walk.score, best)
+                .iter()
+                .map(|line| Widget::label(line)),
+        );
+        widgets.push(Widget::button(GameOverItem::NewGame, "New game (R)"));
+        widgets.push(Widget::button(GameOverItem::MainMenu, "Main menu"));
+        panel(widgets)
+    }
+}
+
+impl Scene<Walk> for GameOver {
+    fn update(&mut self, walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        let ui = match &mut self.ui {
+            Some(ui) => ui,
+            None => {
+                self.ticks += 1;
+                if self.ticks >= GAME_OVER_DELAY {
+                    self.ui = Some(GameOver::panel(walk));
+                }
+                return Transition::Stay;
+            }
+        };
+
+        let event = ui.update(keystate);
+        if keystate.was_just_pressed(RESTART_KEY)
+            || matches!(event, Some(UiEvent::Pressed(GameOverItem::NewGame)))
+        {
+            walk.reset();
+            walk.boy.run_right();
+            Transition::Replace(Box::new(Walking))
+        } else if keystate.was_just_pressed("Escape")
+            || matches!(event, Some(UiEvent::Pressed(GameOverItem::MainMenu)))
+        {
+            Transition::Clear(Box::new(Title::default()))
+        } else {
+            Transition::Stay
+        }
+    }
+
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        walk.draw(renderer);
+        if let Some(ui) = &self.ui {
+            draw_panel(walk, renderer, ui);
+        }
+    }
+
+    fn enter(&mut self, walk: &mut Walk) {
+        if walk.high_scores.insert(walk.score) {
+            if let Err(err) = walk.high_scores.save() {
+                error!("Could not save the high scores {:#?}", err);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    QuitToTitle,
+}
+
+// Drawn over the frozen walk, which carries on exactly where it stopped
+pub struct Paused {
+    ui: Ui<PauseItem>,
+}
+
+impl Default for Paused {
+    fn default() -> Self {
+        Paused {
+            ui: panel(vec![
+                Widget::label("Paused"),
+                Widget::button(PauseItem::Resume, "Resume"),
+                Widget::button(PauseItem::Restart, "Restart"),
+                Widget::button(PauseItem::Settings, "Settings"),
+                Widget::button(PauseItem::QuitToTitle, "Quit to title"),
+            ]),
+        }
+    }
+}
+
+impl Scene<Walk> for Paused {
+    fn update(&mut self, walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        if pause_pressed(keystate) {
+            return Transition::Pop;
+        }
+
+        match self.ui.update(keystate) {
+            Some(UiEvent::Pressed(PauseItem::Resume)) => Transition::Pop,
+            Some(UiEvent::Pressed(PauseItem::Restart)) => {
+                walk.reset();
+                walk.boy.run_right();
+                Transition::Clear(Box::new(Walking))
+            }
+            Some(UiEvent::Pressed(PauseItem::Settings)) => {
+                Transition::Push(Box::new(Settings::new(walk)))
+            }
+            Some(UiEvent::Pressed(PauseItem::QuitToTitle)) => {
+                Transition::Clear(Box::new(Title::default()))
+            }
+            _ => Transition::Stay,
+        }
+    }
+
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        draw_panel(walk, renderer, &self.ui);
+    }
+
+    fn enter(&mut self, walk: &mut Walk) {
+        walk.music.set_dimmed(true);
+    }
+
+    fn exit(&mut self, walk: &mut Walk) {
+        walk.music.set_dimmed(false);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SettingsItem {
+    MusicVolume,
+    Lives,
+    Hitboxes,
+    Back,
+}
+
+// Opened from the title or the pause menu, and goes back to whichever it was
+pub struct Settings {
+    ui: Ui<SettingsItem>,
+}
+
+impl Settings {
+    fn new(walk: &Walk) -> Self {
+        Settings {
+            ui: panel(vec![
+                Widget::label("Settings"),
+                Widget::slider(SettingsItem::MusicVolume, "Music", walk.music.volume()),
+                Widget::toggle(
+                    SettingsItem::Lives,
+                    "Lives",
+                    walk.boy.starting_lives().is_some(),
+                ),
+                Widget::toggle(SettingsItem::Hitboxes, "Show hitboxes", walk.debug.visible),
+                Widget::button(SettingsItem::Back, "Back"),
+            ]),
+        }
+    }
+}
+
+impl Scene<Walk> for Settings {
+    fn update(&mut self, walk: &mut Walk, keystate: &KeyState) -> Transition<Walk> {
+        if pause_pressed(keystate) {
+            return Transition::Pop;
+        }
+
+        match self.ui.update(keystate) {
+            Some(UiEvent::Changed(SettingsItem::MusicVolume, volume)) => {
+                walk.music.set_volume(volume);
+                Transition::Stay
+            }
+            Some(UiEvent::Toggled(SettingsItem::Lives, on)) => {
+                walk.boy
+                    .set_starting_lives(if on { Some(STARTING_LIVES) } else { None });
+                Transition::Stay
+            }
+            Some(UiEvent::Toggled(SettingsItem::Hitboxes, on)) => {
+                walk.debug.visible = on;
+                Transition::Stay
+            }
+            Some(UiEvent::Pressed(SettingsItem::Back)) => Transition::Pop,
+            _ => Transition::Stay,
+        }
+    }
+
+    // Covers the menu it came from rather than stacking another panel on it
+    fn draw(&self, walk: &Walk, renderer: &Renderer) {
+        walk.draw(renderer);
+        draw_panel(walk, renderer, &self.ui);
+    }
+}
@@ -1,18 +1,5 @@
 // use anyhow::{anyhow, Result};
 // use wasm_bindgen::JsValue;
-// use web_sys::HtmlElement;
-
-// pub fn draw_ui(html: &str) -> Result<()> {
-//     Ok(())
-// }
-
-// pub fn hide_ui() -> Result<()> {
-//     Ok(())
-// }
-
-// pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
-//     Err(anyhow!("Not implemented yet"))
-// }
 
 // pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
 //     Err(anyhow!("Not implemented yet"))
@@ -22,24 +9,40 @@
 mod tests {
     use crate::{
         browser,
-        engine::{Audio, Image, Point, Sound, SpriteSheet},
-        game::{GameOver, RedHatBoy, Sheet, Walk, WalkTheDogState},
+        engine::{
+            broad_phase::BroadPhase, scene::Scene, ui::NineSlice, Audio, Image, Point, Sound,
+            SpriteSheet,
+        },
+        game::{
+            debug::DebugOverlay, dog::Dog, high_scores::HighScores, leash::Leash,
+            reachability::Reachability, run_stats::RunStats, scenes::GameOver, ObstaclePool,
+            RedHatBoy, Sheet, Walk, PANEL_BORDER, PANEL_TILES,
+        },
     };
-    use futures::channel::mpsc::unbounded;
     use std::{collections::HashMap, rc::Rc};
     use wasm_bindgen_test::wasm_bindgen_test;
     use web_sys::{AudioBuffer, AudioBufferOptions, HtmlImageElement};
 
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
     #[wasm_bindgen_test]
-    fn test_transition_from_game_over_to_new_game() {
-        let (_, receiver) = unbounded();
+    fn test_game_over_draws_no_html() {
         let image = HtmlImageElement::new().unwrap();
         let audio = Audio::new().unwrap();
         let options = AudioBufferOptions::new(1, 3000.0);
         let sound = Sound {
             buffer: AudioBuffer::new(&options).unwrap(),
         };
+        let music = audio.play_looping_sound(&sound).unwrap();
+        let dog = Dog::new(
+            Sheet {
+                frames: HashMap::new(),
+            },
+            image.clone(),
+            audio.clone(),
+            sound.clone(),
+            0,
+            0,
+        );
         let rhb = RedHatBoy::new(
             Sheet {
                 frames: HashMap::new(),
@@ -47,25 +50,40 @@ mod tests {
             image.clone(),
             audio,
             sound,
+            None,
         );
 
-        let sprite_sheet = SpriteSheet {
+        let sprite_sheet = Rc::new(SpriteSheet {
             sheet: Sheet {
                 frames: HashMap::new(),
             },
             image: image.clone(),
-        };
+            masks: HashMap::new(),
+        });
 
-        let walk = Walk {
+        let leash = Leash::new(rhb.hand(), dog.collar());
+        let mut walk = Walk {
             boy: rhb,
+            dog,
+            leash,
             backgrounds: [
                 Image::new(image.clone(), Point { x: 0, y: 0 }),
                 Image::new(image.clone(), Point { x: 0, y: 0 }),
             ],
             obstacles: vec![],
-            obstacle_sheet: Rc::new(sprite_sheet),
-            stone: image,
+            obstacle_pool: ObstaclePool::default(),
+            broad_phase: BroadPhase::default(),
+            obstacle_sheet: sprite_sheet.clone(),
+            stone: Image::new(image, Point { x: 0, y: 0 }),
             timeline: 0,
+            last_segment: None,
+            reachability: Reachability::new(0, 0),
+            score: 0,
+            debug: DebugOverlay::default(),
+            music,
+            high_scores: HighScores::default(),
+            skin: NineSlice::new(sprite_sheet, PANEL_TILES, PANEL_BORDER),
+            stats: RunStats::default(),
         };
 
         // act
@@ -77,24 +95,15 @@ mod tests {
             .insert_adjacent_html("afterbegin", "<canvas id='canvas'></canvas>")
             .unwrap();
 
-        document
-            .body()
-            .unwrap()
-            .insert_adjacent_html("afterbegin", "<div id='ui'></div>")
-            .unwrap();
-        browser::draw_ui("<p>This is the UI</p>").unwrap();
-
-        let state = WalkTheDogState {
-            _state: GameOver {
-                new_game_event: receiver,
-            },
-            walk,
-        };
+        let body = document.body().unwrap();
+        let elements = body.child_element_count();
 
-        state.new_game();
+        let mut game_over = GameOver::default();
+        game_over.enter(&mut walk);
+        game_over.exit(&mut walk);
 
         // assert
-        let ui = browser::find_html_element_by_id("ui").unwrap();
-        assert_eq!(ui.child_element_count(), 0);
+        // The game over panel is drawn on the canvas, not added to the page
+        assert_eq!(body.child_element_count(), elements);
     }
 }
@@ -5,7 +5,7 @@ mod game;
 mod segments;
 mod sound;
 
-use engine::GameLoop;
+use engine::{profiler, GameLoop};
 use game::WalkTheDog;
 use wasm_bindgen::prelude::*;
 
@@ -22,3 +22,9 @@ pub fn main_js() -> Result<(), JsValue> {
 
     Ok(())
 }
+
+// Frame timings for checking performance from the console, e.g. wasm.profile().fps
+#[wasm_bindgen]
+pub fn profile() -> Result<JsValue, JsValue> {
+    JsValue::from_serde(&profiler::stats()).map_err(|err| JsValue::from_str(&err.to_string()))
+}
@@ -1,77 +1,405 @@
 use std::rc::Rc;
 
-use web_sys::HtmlImageElement;
+use rand::prelude::*;
 
 use crate::{
     engine::{Image, Point, Rect, SpriteSheet},
-    game::{Barrier, Obstacle, Platform},
+    game::{
+        rightmost, Barrier, Coin, Enemy, EnemyKind, Obstacle, ObstaclePool, Overhang, Platform,
+        PlatformKind, PowerUpPickup, Trigger, TriggerZone, FLYER_BOB, FLYER_HEIGHT, GROUND_TOP,
+        POWER_UPS,
+    },
 };
 
-const FIRST_PLATFORM: i16 = 500;
-const HIGH_PLATFORM: i16 = 375;
-const LOW_PLATFORM: i16 = 420;
+const FIRST_PLATFORM: i32 = 500;
+const HIGH_PLATFORM: i32 = GROUND_TOP - 225;
+const LOW_PLATFORM: i32 = GROUND_TOP - 180;
+const INITIAL_STONE_OFFSET: i32 = 150;
+const STONE_ON_GROUND: i32 = GROUND_TOP - 54;
+const FLOATING_PLATFORM_SPRITES: [&str; 3] = ["13.png", "14.png", "15.png"];
+const GROUND_TILE: i32 = 128;
+const PIT_WIDTH: i32 = GROUND_TILE;
+const GROUND: [&str; 7] = ["2.png"; 7];
+const OPEN_GROUND: [&str; 3] = ["2.png"; 3];
+const GROUND_BEFORE_PIT: [&str; 3] = ["2.png", "2.png", "3.png"];
+const GROUND_AFTER_PIT: [&str; 3] = ["1.png", "2.png", "2.png"];
+const SPRINGBOARD: [&str; 1] = ["2.png"];
+// Too wide to jump, the moving platform in the middle has to be ridden across
+const WIDE_PIT: i32 = 4 * GROUND_TILE;
+const MOVING_PLATFORM_OFFSET: i32 = 64;
+// Only a springboard gets the boy this high
+const SKY_COINS: i32 = 60;
+const COIN_SPACING: i32 = 40;
+const TRIGGER_WIDTH: i32 = 10;
+const POWER_UP_CHANCE: f64 = 0.3;
+const OVERHANG_OFFSET: i32 = 400;
+const OVERHANG_WIDTH: i32 = 48;
+// Low enough to hit a running boy, high enough for a sliding one to pass under
+const OVERHANG_BOTTOM: i32 = GROUND_TOP - 65;
+const COIN_UNDER_OVERHANG: i32 = GROUND_TOP - 30;
+const ENEMY_OFFSET: i32 = 500;
+const WALKER_ON_GROUND: i32 = GROUND_TOP - 36;
+const JUMPER_ON_GROUND: i32 = GROUND_TOP - 40;
+// As low as an overhang at the bottom of its bob
+const FLYER_TOP: i32 = OVERHANG_BOTTOM - FLYER_HEIGHT - FLYER_BOB;
+// Just past the coins at the end of a floating platform
+const POWER_UP_OFFSET: i32 = FIRST_PLATFORM + 92 + 6 * COIN_SPACING;
 
-pub fn stone_and_platform(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    const STONE_ON_GROUND: i16 = 546;
-
-    let position = Point {
-        x: offset_x + INITIAL_STONE_OFFSET,
-        y: STONE_ON_GROUND,
-    };
-    let stone = Image::new(stone, position);
-    let stone = Barrier::new(stone);
-
-    let position = Point {
-        x: offset_x + FIRST_PLATFORM,
-        y: LOW_PLATFORM,
-    };
-    vec![
-        Box::new(stone),
-        Box::new(create_floating_platform(sprite_sheet, position)),
-    ]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    StoneAndPlatform,
+    PlatformAndStone,
+    Pit,
+    Overhang,
+    Walker,
+    Jumper,
+    Flyer,
+    MovingPlatform,
+    CrumblingPlatform,
+    Springboard,
+    OpenGround,
 }
 
-pub fn platform_and_stone(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    const STONE_ON_GROUND: i16 = 546;
-
-    let position = Point {
-        x: offset_x + INITIAL_STONE_OFFSET,
-        y: STONE_ON_GROUND,
-    };
-    let stone = Image::new(stone, position);
-    let stone = Barrier::new(stone);
-
-    let position = Point {
-        x: offset_x + FIRST_PLATFORM,
-        y: HIGH_PLATFORM,
-    };
-    vec![
-        Box::new(stone),
-        Box::new(create_floating_platform(sprite_sheet, position)),
-    ]
+// Open ground is left out, it's only used when nothing else can safely follow
+pub const SEGMENTS: [Segment; 10] = [
+    Segment::StoneAndPlatform,
+    Segment::PlatformAndStone,
+    Segment::Pit,
+    Segment::Overhang,
+    Segment::Walker,
+    Segment::Jumper,
+    Segment::Flyer,
+    Segment::MovingPlatform,
+    Segment::CrumblingPlatform,
+    Segment::Springboard,
+];
+
+// A segment is described as plain geometry first so that it can be validated
+// without any images loaded, and only then turned into obstacles.
+#[derive(Clone, Copy, Debug)]
+pub enum Piece {
+    Stone(Point),
+    FloatingPlatform(Point, PlatformKind),
+    Ground(Point, &'static [&'static str]),
+    // A tile of ground that launches the boy
+    Springboard(Point),
+    Coin(Point),
+    Overhang(Rect),
+    // Only a place a power up may spawn, which one and whether it does is random
+    PowerUp(Point),
+    // Where the enemy starts, it moves once it's on screen
+    Enemy(EnemyKind, Point),
+    // Added after layout, once the segment's obstacles say where it ends
+    Trigger(Rect, Trigger),
 }
 
-fn create_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
-    const FLOATING_PLATFORM_SPRITES: [&str; 3] = ["13.png", "14.png", "15.png"];
+impl Segment {
+    pub fn layout(&self, offset_x: i32) -> Vec<Piece> {
+        let stone = Piece::Stone(Point {
+            x: offset_x + INITIAL_STONE_OFFSET,
+            y: STONE_ON_GROUND,
+        });
+        let ground = |x: i32, tiles: &'static [&'static str]| {
+            Piece::Ground(
+                Point {
+                    x: offset_x + x,
+                    y: GROUND_TOP,
+                },
+                tiles,
+            )
+        };
+
+        let coins = |x: i32, y: i32, count: i32| {
+            (0..count).map(move |coin| {
+                Piece::Coin(Point {
+                    x: offset_x + x + coin * COIN_SPACING,
+                    y,
+                })
+            })
+        };
+
+        let enemy = |kind: EnemyKind, y: i32| {
+            Piece::Enemy(
+                kind,
+                Point {
+                    x: offset_x + ENEMY_OFFSET,
+                    y,
+                },
+            )
+        };
+
+        match self {
+            Segment::StoneAndPlatform => {
+                let mut pieces = vec![
+                    ground(0, &GROUND),
+                    stone,
+                    Piece::FloatingPlatform(
+                        Point {
+                            x: offset_x + FIRST_PLATFORM,
+                            y: LOW_PLATFORM,
+                        },
+                        PlatformKind::Fixed,
+                    ),
+                ];
+                pieces.extend(coins(INITIAL_STONE_OFFSET + 5, STONE_ON_GROUND - 120, 3));
+                pieces.extend(coins(FIRST_PLATFORM + 92, LOW_PLATFORM - 40, 6));
+                pieces.push(Piece::PowerUp(Point {
+                    x: offset_x + POWER_UP_OFFSET,
+                    y: LOW_PLATFORM - 40,
+                }));
+                pieces
+            }
+            Segment::PlatformAndStone => {
+                let mut pieces = vec![
+                    ground(0, &GROUND),
+                    stone,
+                    Piece::FloatingPlatform(
+                        Point {
+                            x: offset_x + FIRST_PLATFORM,
+                            y: HIGH_PLATFORM,
+                        },
+                        PlatformKind::Fixed,
+                    ),
+                ];
+                pieces.extend(coins(INITIAL_STONE_OFFSET + 5, STONE_ON_GROUND - 120, 3));
+                pieces.extend(coins(FIRST_PLATFORM + 92, HIGH_PLATFORM - 40, 6));
+                pieces.push(Piece::PowerUp(Point {
+                    x: offset_x + POWER_UP_OFFSET,
+                    y: HIGH_PLATFORM - 40,
+                }));
+                pieces
+            }
+            Segment::Pit => {
+                let pit_x = GROUND_BEFORE_PIT.len() as i32 * GROUND_TILE;
+                let mut pieces = vec![
+                    ground(0, &GROUND_BEFORE_PIT),
+                    ground(pit_x + PIT_WIDTH, &GROUND_AFTER_PIT),
+                ];
+                pieces.extend(coins(pit_x + 24, GROUND_TOP - 200, 3));
+                pieces
+            }
+            Segment::Overhang => {
+                let mut pieces = vec![
+                    ground(0, &GROUND),
+                    Piece::Overhang(Rect::new_from_x_y(
+                        offset_x + OVERHANG_OFFSET,
+                        0,
+                        OVERHANG_WIDTH,
+                        OVERHANG_BOTTOM,
+                    )),
+                ];
+                pieces.extend(coins(OVERHANG_OFFSET - 16, COIN_UNDER_OVERHANG, 3));
+                pieces
+            }
+            Segment::Walker => vec![
+                ground(0, &GROUND),
+                enemy(EnemyKind::Walker, WALKER_ON_GROUND),
+            ],
+            Segment::Jumper => vec![
+                ground(0, &GROUND),
+                enemy(EnemyKind::Jumper, JUMPER_ON_GROUND),
+            ],
+            Segment::Flyer => vec![ground(0, &GROUND), enemy(EnemyKind::Flyer, FLYER_TOP)],
+            Segment::MovingPlatform => {
+                let pit_x = GROUND_BEFORE_PIT.len() as i32 * GROUND_TILE;
+                vec![
+                    ground(0, &GROUND_BEFORE_PIT),
+                    Piece::FloatingPlatform(
+                        Point {
+                            x: offset_x + pit_x + MOVING_PLATFORM_OFFSET,
+                            y: LOW_PLATFORM,
+                        },
+                        PlatformKind::Moving,
+                    ),
+                    ground(pit_x + WIDE_PIT, &GROUND_AFTER_PIT),
+                ]
+            }
+            // The crumbling platform is only a detour for its coins, the ground
+            // underneath is always there
+            Segment::CrumblingPlatform => {
+                let mut pieces = vec![
+                    ground(0, &GROUND),
+                    stone,
+                    Piece::FloatingPlatform(
+                        Point {
+                            x: offset_x + FIRST_PLATFORM,
+                            y: HIGH_PLATFORM,
+                        },
+                        PlatformKind::Crumbling,
+                    ),
+                ];
+                pieces.extend(coins(INITIAL_STONE_OFFSET + 5, STONE_ON_GROUND - 120, 3));
+                pieces.extend(coins(FIRST_PLATFORM + 92, HIGH_PLATFORM - 40, 6));
+                pieces
+            }
+            Segment::Springboard => {
+                let springboard_x = GROUND_BEFORE_PIT.len() as i32 * GROUND_TILE;
+                let mut pieces = vec![
+                    ground(0, &GROUND_BEFORE_PIT),
+                    Piece::Springboard(Point {
+                        x: offset_x + springboard_x,
+                        y: GROUND_TOP,
+                    }),
+                    ground(springboard_x + GROUND_TILE, &GROUND_AFTER_PIT),
+                ];
+                pieces.extend(coins(springboard_x + GROUND_TILE, SKY_COINS, 4));
+                pieces
+            }
+            Segment::OpenGround => vec![ground(0, &OPEN_GROUND)],
+        }
+    }
+
+    // Obstacles are taken from the pool when there's a spare one of the right
+    // kind, and only allocated when there isn't
+    pub fn obstacles(
+        &self,
+        stone: &Image,
+        sprite_sheet: Rc<SpriteSheet>,
+        offset_x: i32,
+        pool: &mut ObstaclePool,
+    ) -> Vec<Box<dyn Obstacle>> {
+        let mut rng = thread_rng();
+        let mut obstacles: Vec<Box<dyn Obstacle>> = self
+            .layout(offset_x)
+            .into_iter()
+            .filter_map(|piece| -> Option<Box<dyn Obstacle>> {
+                // Decided before anything is taken from the pool, which only
+                // knows where a power up goes
+                if matches!(piece, Piece::PowerUp(_)) && !rng.gen_bool(POWER_UP_CHANCE) {
+                    return None;
+                }
+                if let Some(obstacle) = pool.spawn(&piece) {
+                    return Some(obstacle);
+                }
+
+                match piece {
+                    Piece::Stone(position) => {
+                        let mut image = stone.clone();
+                        image.set_position(position);
+                        Some(Box::new(Barrier::new(image)))
+                    }
+                    Piece::FloatingPlatform(position, kind) => Some(Box::new(
+                        create_floating_platform(sprite_sheet.clone(), position).with_kind(kind),
+                    )),
+                    Piece::Ground(position, tiles) => Some(Box::new(create_ground(
+                        sprite_sheet.clone(),
+                        position,
+                        tiles,
+                    ))),
+                    Piece::Springboard(position) => Some(Box::new(
+                        create_ground(sprite_sheet.clone(), position, &SPRINGBOARD)
+                            .with_kind(PlatformKind::Springboard),
+                    )),
+                    Piece::Overhang(bounding_box) => Some(Box::new(Overhang::new(bounding_box))),
+                    Piece::Coin(center) => Some(Box::new(Coin::new(center))),
+                    Piece::Enemy(kind, origin) => Some(Box::new(Enemy::new(kind, origin))),
+                    Piece::PowerUp(center) => {
+                        POWER_UPS
+                            .choose(&mut rng)
+                            .map(|&power_up| -> Box<dyn Obstacle> {
+                                Box::new(PowerUpPickup::new(center, power_up))
+                            })
+                    }
+                    Piece::Trigger(bounding_box, trigger) => {
+                        Some(Box::new(TriggerZone::new(bounding_box, trigger)))
+                    }
+                }
+            })
+            .collect();
+
+        // Clearing a segment is worth points, so its last few pixels fire a trigger
+        let right = rightmost(&obstacles);
+        let cleared = Rect::new_from_x_y(right - TRIGGER_WIDTH, 0, TRIGGER_WIDTH, GROUND_TOP);
+        let trigger = Piece::Trigger(cleared, Trigger::SegmentCleared);
+        obstacles.push(
+            pool.spawn(&trigger)
+                .unwrap_or_else(|| Box::new(TriggerZone::new(cleared, Trigger::SegmentCleared))),
+        );
+        obstacles
+    }
+}
+
+impl Piece {
+    pub fn is_lethal(&self) -> bool {
+        matches!(
+            self,
+            Piece::Stone(_) | Piece::Overhang(_) | Piece::Enemy(_, _)
+        )
+    }
+
+    pub fn is_solid(&self) -> bool {
+        matches!(
+            self,
+            Piece::FloatingPlatform(_, _) | Piece::Ground(_, _) | Piece::Springboard(_)
+        )
+    }
+
+    // The stone's size is only known once its image is loaded, so it is passed in
+    pub fn bounding_boxes(&self, stone_width: i32, stone_height: i32) -> Vec<Rect> {
+        match self {
+            Piece::Stone(position) => vec![Rect::new(*position, stone_width, stone_height)],
+            Piece::FloatingPlatform(position, _) => floating_platform_bounding_boxes()
+                .iter()
+                .map(|bounding_box| {
+                    Rect::new_from_x_y(
+                        bounding_box.x() + position.x,
+                        bounding_box.y() + position.y,
+                        bounding_box.width(),
+                        bounding_box.height(),
+                    )
+                })
+                .collect(),
+            Piece::Ground(position, tiles) => vec![ground_bounding_box(*position, tiles)],
+            Piece::Springboard(position) => vec![ground_bounding_box(*position, &SPRINGBOARD)],
+            Piece::Overhang(bounding_box) => vec![*bounding_box],
+            Piece::Enemy(kind, origin) => vec![kind.resting_box(*origin)],
+            Piece::Coin(_) | Piece::PowerUp(_) | Piece::Trigger(_, _) => vec![],
+        }
+    }
+
+    // Where a platform goes and what it's made of, so one platform can be
+    // reused for another made of the same tiles
+    pub fn platform(&self) -> Option<(Point, &'static [&'static str], PlatformKind)> {
+        match *self {
+            Piece::FloatingPlatform(position, kind) => {
+                Some((position, &FLOATING_PLATFORM_SPRITES, kind))
+            }
+            Piece::Ground(position, tiles) => Some((position, tiles, PlatformKind::Fixed)),
+            Piece::Springboard(position) => {
+                Some((position, &SPRINGBOARD, PlatformKind::Springboard))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn ground_bounding_box(position: Point, tiles: &[&str]) -> Rect {
+    Rect::new(position, tiles.len() as i32 * GROUND_TILE, GROUND_TILE)
+}
+
+fn floating_platform_bounding_boxes() -> [Rect; 3] {
     let first = Rect::new_from_x_y(0, 0, 60, 54);
     let second = Rect::new_from_x_y(60, 0, 384 - (60 * 2), 93);
     let third = Rect::new_from_x_y(384 - 60, 0, 60, 54);
-    let floating_platform_bounding_boxes: [Rect; 3] = [first, second, third];
+    [first, second, third]
+}
 
+fn create_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
     Platform::new(
         sprite_sheet,
         position,
         &FLOATING_PLATFORM_SPRITES,
-        &floating_platform_bounding_boxes,
+        &floating_platform_bounding_boxes(),
     )
 }
+
+// Ground is a platform that happens to sit at the bottom of the screen, the boy
+// lands on it the same way and dies if he misses it
+fn create_ground(
+    sprite_sheet: Rc<SpriteSheet>,
+    position: Point,
+    tiles: &'static [&'static str],
+) -> Platform {
+    let bounding_box = ground_bounding_box(Point { x: 0, y: 0 }, tiles);
+    Platform::new(sprite_sheet, position, tiles, &[bounding_box])
+}
@@ -12,7 +12,7 @@ use anyhow::{anyhow, Result};
 use js_sys::ArrayBuffer;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode, AudioNode};
+use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, AudioNode, GainNode};
 
 pub async fn decode_audio_data(
     ctx: &AudioContext,
@@ -34,8 +34,13 @@ pub enum LOOPING {
     Yes,
 }
 
-pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: LOOPING) -> Result<()> {
-    let track_source = create_track_source(ctx, buffer)?;
+pub fn play_sound(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    looping: LOOPING,
+    destination: &AudioNode,
+) -> Result<()> {
+    let track_source = create_track_source(ctx, buffer, destination)?;
 
     if matches!(looping, LOOPING::Yes) {
         track_source.set_loop(true);
@@ -46,13 +51,27 @@ pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: LOOPING) ->
         .map_err(|err| anyhow!("Could not start sound! {:#?}", err))
 }
 
-fn create_track_source(ctx: &AudioContext, buffer: &AudioBuffer) -> Result<AudioBufferSourceNode> {
+fn create_track_source(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    destination: &AudioNode,
+) -> Result<AudioBufferSourceNode> {
     let track_source = create_buffer_source(ctx)?;
     track_source.set_buffer(Some(buffer));
-    connect_with_audio_node(&track_source, &ctx.destination())?;
+    connect_with_audio_node(&track_source, destination)?;
     Ok(track_source)
 }
 
+// A volume control between whatever plays into it and the speakers
+pub fn create_gain(ctx: &AudioContext) -> Result<GainNode> {
+    let gain = ctx
+        .create_gain()
+        .map_err(|err| anyhow!("Error creating gain node {:#?}", err))?;
+    gain.connect_with_audio_node(&ctx.destination())
+        .map_err(|err| anyhow!("Error connecting gain to destination {:#?}", err))?;
+    Ok(gain)
+}
+
 pub fn create_audio_context() -> Result<AudioContext> {
     AudioContext::new().map_err(|err| anyhow!("Could not create audio context: {:#?}", err))
 }
@@ -64,7 +83,7 @@ fn create_buffer_source(ctx: &AudioContext) -> Result<AudioBufferSourceNode> {
 
 fn connect_with_audio_node(
     buffer_source: &AudioBufferSourceNode,
-    destination: &AudioDestinationNode,
+    destination: &AudioNode,
 ) -> Result<AudioNode> {
     buffer_source
         .connect_with_audio_node(destination)